@@ -1,4 +1,4 @@
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
 use hyper::client::connect::dns::GaiResolver;
 use hyper::client::HttpConnector;
 use hyper::header::HeaderName;
@@ -46,7 +46,7 @@ fn generate_string() -> String {
 }
 
 fn build_headers() -> HeaderMap {
-    let mut headers_map: HeaderMap = (&*internal_benches::hop_headers())
+    let mut headers_map: HeaderMap = internal_benches::hop_headers()
         .iter()
         .map(|el: &'static HeaderName| (el.clone(), generate_string().parse().unwrap()))
         .collect();
@@ -66,6 +66,55 @@ fn build_headers() -> HeaderMap {
     headers_map
 }
 
+fn build_typical_request_headers() -> HeaderMap {
+    let mut headers_map = HeaderMap::new();
+
+    for (name, value) in [
+        ("host", "example.com"),
+        ("user-agent", "Mozilla/5.0 (X11; Linux x86_64)"),
+        ("accept", "text/html,application/xhtml+xml"),
+        ("accept-language", "en-US,en;q=0.5"),
+        ("accept-encoding", "gzip, deflate, br"),
+        ("cookie", "session=0123456789abcdef"),
+        ("referer", "https://example.com/"),
+        ("x-request-id", "3f2a9c1e"),
+    ] {
+        headers_map.insert(name, value.parse().unwrap());
+    }
+
+    headers_map
+}
+
+fn remove_hop_headers(b: &mut Criterion) {
+    let header_sets = [
+        ("typical request", build_typical_request_headers()),
+        ("typical request with hop headers", {
+            let mut headers_map = build_typical_request_headers();
+            headers_map.insert("connection", "keep-alive".parse().unwrap());
+            headers_map.insert("keep-alive", "timeout=5".parse().unwrap());
+            headers_map
+        }),
+        ("hop and random headers", build_headers()),
+    ];
+
+    for (name, headers_map) in header_sets {
+        b.bench_function(&format!("remove hop headers, {}", name), |t| {
+            t.iter_batched_ref(
+                || headers_map.clone(),
+                |headers| internal_benches::remove_hop_headers(black_box(headers)),
+                BatchSize::SmallInput,
+            )
+        });
+        b.bench_function(&format!("remove hop headers by lookup, {}", name), |t| {
+            t.iter_batched_ref(
+                || headers_map.clone(),
+                |headers| internal_benches::remove_hop_headers_by_lookup(black_box(headers)),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+}
+
 fn proxy_call(b: &mut Criterion) {
     let rt = Runtime::new().unwrap();
 
@@ -212,6 +261,7 @@ fn create_proxied_request_forwarded_for_vacant(b: &mut Criterion) {
 
 criterion_group!(external_api, proxy_call);
 criterion_group!(responses, create_proxied_response);
+criterion_group!(headers, remove_hop_headers);
 criterion_group!(
     url_parsing,
     forward_url_with_query,
@@ -224,4 +274,4 @@ criterion_group!(
     create_proxied_request_forwarded_for_vacant,
     create_proxied_request_forwarded_for_occupied
 );
-criterion_main!(external_api, responses, url_parsing, requests);
+criterion_main!(external_api, responses, headers, url_parsing, requests);
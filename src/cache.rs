@@ -0,0 +1,172 @@
+//! Caching of backend responses for `ReverseProxy::call_cached`.
+
+use hyper::body::Bytes;
+use hyper::header::{HeaderMap, CACHE_CONTROL, SET_COOKIE, VARY};
+use hyper::{Body, Response, StatusCode};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A fully buffered response as stored in a [`ResponseCache`].
+#[derive(Debug, Clone)]
+pub struct CachedResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl CachedResponse {
+    /// Turns the stored response into one to answer the client with.
+    pub fn into_response(self) -> Response<Body> {
+        let mut response = Response::new(Body::from(self.body));
+        *response.status_mut() = self.status;
+        *response.headers_mut() = self.headers;
+
+        response
+    }
+}
+
+/// Stores responses for `ReverseProxy::call_cached`, keyed by method and forward URI.
+///
+/// Methods are called on the request path and should return quickly.
+pub trait ResponseCache: Send + Sync {
+    /// The response stored under `key`, `None` when there is none or it expired.
+    fn get(&self, key: &str) -> Option<CachedResponse>;
+
+    /// Stores `response` under `key` for `ttl`.
+    fn put(&self, key: &str, response: CachedResponse, ttl: Duration);
+}
+
+/// An in-memory `ResponseCache` evicting the least recently used entry once `capacity` entries
+/// are stored.
+pub struct LruCache {
+    capacity: usize,
+    state: Mutex<LruState>,
+}
+
+#[derive(Default)]
+struct LruState {
+    entries: HashMap<String, (CachedResponse, Instant)>,
+    // least recently used first
+    order: VecDeque<String>,
+}
+
+impl LruState {
+    fn remove(&mut self, key: &str) {
+        self.entries.remove(key);
+        self.order.retain(|entry| entry != key);
+    }
+}
+
+impl LruCache {
+    /// Creates a cache holding at most `capacity` responses, `0` stores nothing.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            state: Mutex::new(LruState::default()),
+        }
+    }
+}
+
+impl ResponseCache for LruCache {
+    fn get(&self, key: &str) -> Option<CachedResponse> {
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        let (response, expires) = state.entries.get(key)?;
+
+        if *expires <= Instant::now() {
+            state.remove(key);
+            return None;
+        }
+
+        let response = response.clone();
+        state.order.retain(|entry| entry != key);
+        state.order.push_back(key.to_string());
+
+        Some(response)
+    }
+
+    fn put(&self, key: &str, response: CachedResponse, ttl: Duration) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        state.remove(key);
+
+        while state.entries.len() >= self.capacity {
+            match state.order.pop_front() {
+                Some(evicted) => {
+                    state.entries.remove(&evicted);
+                }
+                None => break,
+            }
+        }
+
+        state
+            .entries
+            .insert(key.to_string(), (response, Instant::now() + ttl));
+        state.order.push_back(key.to_string());
+    }
+}
+
+/// The directives of all `Cache-Control` headers, lowercased.
+fn directives(headers: &HeaderMap) -> Vec<String> {
+    headers
+        .get_all(CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|directive| directive.trim().to_ascii_lowercase())
+        .filter(|directive| !directive.is_empty())
+        .collect()
+}
+
+/// Whether the request allows answering from the cache and storing its response.
+pub(crate) fn request_allows_cache(headers: &HeaderMap) -> bool {
+    !directives(headers)
+        .iter()
+        .any(|directive| directive == "no-store" || directive == "no-cache")
+}
+
+/// Whether a shared cache may answer requests carrying `Authorization` with the response, which
+/// takes `public` or `s-maxage` (RFC 7234 section 3.2).
+pub(crate) fn shared_with_authorization(headers: &HeaderMap) -> bool {
+    directives(headers)
+        .iter()
+        .any(|directive| directive == "public" || directive.starts_with("s-maxage"))
+}
+
+/// How long a response may be cached: its `s-maxage`, else `max-age`. `None` when it must not be
+/// stored or declares no lifetime.
+///
+/// Responses with `Vary` are never stored, as the key does not cover the request headers they
+/// vary on, nor are responses setting cookies meant for a single client.
+pub(crate) fn response_ttl(headers: &HeaderMap) -> Option<Duration> {
+    if headers.contains_key(VARY) || headers.contains_key(SET_COOKIE) {
+        return None;
+    }
+
+    let directives = directives(headers);
+
+    if directives.iter().any(|directive| {
+        directive == "no-store" || directive == "no-cache" || directive == "private"
+    }) {
+        return None;
+    }
+
+    let max_age = |name: &str| {
+        directives.iter().find_map(|directive| {
+            let (key, value) = directive.split_once('=')?;
+            if key.trim() == name {
+                value.trim().trim_matches('"').parse::<u64>().ok()
+            } else {
+                None
+            }
+        })
+    };
+
+    max_age("s-maxage")
+        .or_else(|| max_age("max-age"))
+        .filter(|seconds| *seconds > 0)
+        .map(Duration::from_secs)
+}
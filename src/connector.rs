@@ -0,0 +1,154 @@
+//! A type erased connector, for choosing the connector of a `ReverseProxy` at runtime.
+
+use futures_util::future::BoxFuture;
+use hyper::client::connect::{Connected, Connection};
+use hyper::service::Service;
+use hyper::Uri;
+use std::error::Error;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+type BoxError = Box<dyn Error + Send + Sync>;
+
+trait Io: AsyncRead + AsyncWrite + Connection + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Connection + Send + Unpin> Io for T {}
+
+/// A connection returned by a [`BoxConnector`].
+pub struct BoxConnection(Box<dyn Io>);
+
+impl Connection for BoxConnection {
+    fn connected(&self) -> Connected {
+        self.0.connected()
+    }
+}
+
+impl AsyncRead for BoxConnection {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.0).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for BoxConnection {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut *self.0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.0).poll_shutdown(cx)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut *self.0).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.0.is_write_vectored()
+    }
+}
+
+trait CloneConnector: Send + Sync {
+    fn clone_box(&self) -> Box<dyn CloneConnector>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), BoxError>>;
+
+    fn call(&mut self, uri: Uri) -> BoxFuture<'static, Result<BoxConnection, BoxError>>;
+}
+
+impl<C> CloneConnector for C
+where
+    C: Service<Uri> + Clone + Send + Sync + 'static,
+    C::Response: AsyncRead + AsyncWrite + Connection + Send + Unpin + 'static,
+    C::Error: Into<BoxError>,
+    C::Future: Send + 'static,
+{
+    fn clone_box(&self) -> Box<dyn CloneConnector> {
+        Box::new(self.clone())
+    }
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), BoxError>> {
+        Service::poll_ready(self, cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, uri: Uri) -> BoxFuture<'static, Result<BoxConnection, BoxError>> {
+        let connecting = Service::call(self, uri);
+
+        Box::pin(async move {
+            match connecting.await {
+                Ok(connection) => Ok(BoxConnection(Box::new(connection))),
+                Err(error) => Err(error.into()),
+            }
+        })
+    }
+}
+
+/// A connector wrapping any other, so a single `ReverseProxy<BoxConnector>` can use e.g. an
+/// `HttpConnector` or an HTTPS connector depending on configuration.
+///
+/// Type erasure costs a heap allocation per connection attempt and dynamic dispatch on every read
+/// and write of the connection, which is small next to the network I/O itself.
+///
+/// ```
+/// use hyper::client::HttpConnector;
+/// use hyper_reverse_proxy::{BoxConnector, ReverseProxy};
+///
+/// let proxy: ReverseProxy<BoxConnector> = ReverseProxy::boxed(HttpConnector::new());
+/// ```
+pub struct BoxConnector(Box<dyn CloneConnector>);
+
+impl BoxConnector {
+    /// Wraps `connector`.
+    pub fn new<C>(connector: C) -> Self
+    where
+        C: Service<Uri> + Clone + Send + Sync + 'static,
+        C::Response: AsyncRead + AsyncWrite + Connection + Send + Unpin + 'static,
+        C::Error: Into<BoxError>,
+        C::Future: Send + 'static,
+    {
+        Self(Box::new(connector))
+    }
+}
+
+impl Clone for BoxConnector {
+    fn clone(&self) -> Self {
+        Self(self.0.clone_box())
+    }
+}
+
+impl std::fmt::Debug for BoxConnector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BoxConnector").finish_non_exhaustive()
+    }
+}
+
+impl Service<Uri> for BoxConnector {
+    type Response = BoxConnection;
+    type Error = BoxError;
+    type Future = BoxFuture<'static, Result<BoxConnection, BoxError>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_ready(cx)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        self.0.call(uri)
+    }
+}
@@ -28,6 +28,14 @@
 //! hyper-reverse-proxy = { version = "0.4", features = ["https"] }
 //! ```
 //!
+//! The default `http` feature enables hyper's TCP connector and HTTP/2 client, which `oneshot`,
+//! `ReverseProxyBuilder` and `PinnedResolver` build on. Without it hyper is only pulled in with
+//! its HTTP/1 client, for proxies bringing their own connector:
+//!
+//! ```toml
+//! hyper-reverse-proxy = { version = "0.5", default-features = false }
+//! ```
+//!
 //! The following example will set up a reverse proxy listening on `127.0.0.1:13900`,
 //! and will proxy these calls:
 //!
@@ -111,14 +119,61 @@
 #[macro_use]
 extern crate tracing;
 
-use hyper::header::{HeaderMap, HeaderName, HeaderValue, HOST};
+#[cfg(feature = "cache")]
+mod cache;
+mod connector;
+#[cfg(feature = "metrics")]
+mod metrics;
+mod proxy_protocol;
+#[cfg(feature = "http")]
+mod resolver;
+#[cfg(feature = "signing")]
+mod signing;
+mod transform;
+
+#[cfg(feature = "cache")]
+pub use cache::{CachedResponse, LruCache, ResponseCache};
+pub use connector::{BoxConnection, BoxConnector};
+#[cfg(feature = "metrics")]
+pub use metrics::{NoopMetrics, ProxyMetrics};
+pub use proxy_protocol::{client_ip_from_proxy_protocol, ProxyProtocolPreamble};
+#[cfg(feature = "http")]
+pub use resolver::PinnedResolver;
+
+#[cfg(feature = "regex")]
+pub use regex::Regex;
+#[cfg(feature = "signing")]
+pub use signing::HmacSigner;
+pub use tokio_util::sync::CancellationToken;
+pub use transform::BodyTransform;
+
+use futures_util::future::BoxFuture;
+use hyper::body::{Bytes, HttpBody};
+#[cfg(feature = "http")]
+use hyper::client::connect::HttpInfo;
+#[cfg(feature = "http")]
+use hyper::client::HttpConnector;
+use hyper::header::{
+    HeaderMap, HeaderName, HeaderValue, ACCEPT_ENCODING, AUTHORIZATION, CONTENT_LENGTH, COOKIE,
+    HOST, LOCATION, PROXY_AUTHENTICATE, PROXY_AUTHORIZATION, RETRY_AFTER, SERVER, SET_COOKIE,
+    TRANSFER_ENCODING, USER_AGENT, VIA,
+};
 use hyper::http::header::{InvalidHeaderValue, ToStrError};
-use hyper::http::uri::InvalidUri;
+use hyper::http::uri::{InvalidUri, Scheme};
+use hyper::service::Service;
 use hyper::upgrade::OnUpgrade;
-use hyper::{Body, Client, Error, Request, Response, StatusCode};
+use hyper::{Body, Client, Error, Method, Request, Response, StatusCode, Uri, Version};
+use ipnet::IpNet;
 use lazy_static::lazy_static;
-use std::net::IpAddr;
-use tokio::io::copy_bidirectional;
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU16, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::{Notify, OwnedSemaphorePermit, Semaphore};
 
 lazy_static! {
     static ref TE_HEADER: HeaderName = HeaderName::from_static("te");
@@ -140,14 +195,184 @@ lazy_static! {
     ];
 
     static ref X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+    static ref X_REAL_IP: HeaderName = HeaderName::from_static("x-real-ip");
+    static ref X_FORWARDED_PROTO: HeaderName = HeaderName::from_static("x-forwarded-proto");
+    static ref X_FORWARDED_HOST: HeaderName = HeaderName::from_static("x-forwarded-host");
+    static ref X_CLIENT_CERT_SUBJECT: HeaderName = HeaderName::from_static("x-client-cert-subject");
+    static ref X_CLIENT_CERT_FINGERPRINT: HeaderName =
+        HeaderName::from_static("x-client-cert-fingerprint");
+    static ref SEC_WEBSOCKET_PROTOCOL: HeaderName = HeaderName::from_static("sec-websocket-protocol");
 }
 
 #[derive(Debug)]
 pub enum ProxyError {
     InvalidUri(InvalidUri),
     HyperError(Error),
+    /// The connection to the backend could not be established (refused, DNS or TLS failure, ...).
+    ConnectError {
+        /// The URI the request was forwarded to, or the authority of a tunneled `CONNECT`.
+        uri: String,
+        /// The client's error, or the I/O error of a `CONNECT` tunnel.
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// The backend did not answer in time.
+    Timeout {
+        /// The URI the request was forwarded to.
+        uri: String,
+        source: Error,
+    },
     ForwardHeaderError,
     UpgradeError(String),
+    /// `verify_upstream` could not establish a connection, carries the connector's error.
+    UpstreamUnavailable(Box<dyn std::error::Error + Send + Sync>),
+    /// The request body exceeded the configured `max_request_body`, maps to `413 Payload Too Large`.
+    RequestBodyTooLarge,
+    /// With `normalize_path`, the request path escaped above the forward URL's base path, maps to
+    /// `400 Bad Request`.
+    InvalidPath,
+    /// The call was cancelled through the token passed to `call_with_cancel`.
+    Cancelled,
+    /// The response body exceeded the `max_bytes` passed to `call_buffered`.
+    ResponseBodyTooLarge,
+    /// The scheme of the forward URI is not in the configured `allowed_schemes`.
+    DisallowedScheme(String),
+    /// The forward URI parsed, but cannot be proxied to, e.g. because it has no scheme.
+    InvalidForwardUri {
+        uri: String,
+        reason: &'static str,
+    },
+    /// Building a request or response failed, e.g. in a user hook using `Response::builder()`.
+    BuildError(hyper::http::Error),
+    /// No request body data arrived within the configured `request_body_read_timeout`, maps to
+    /// `408 Request Timeout`.
+    RequestBodyTimeout,
+    /// With `block_private_addresses`, the forward URI resolved to a loopback, link-local or
+    /// private address.
+    ForbiddenAddress(IpAddr),
+    /// The request carried more than one `Host` header, maps to `400 Bad Request` as required by
+    /// RFC 7230 section 5.4.
+    DuplicateHost,
+    /// `call_weighted` was given no upstream with a positive weight, maps to `503 Service
+    /// Unavailable`.
+    NoUpstream,
+    /// With `circuit_breaker`, the forward URI failed too often and is skipped during the
+    /// cooldown, maps to `503 Service Unavailable`.
+    CircuitOpen(String),
+    /// With `max_concurrent_per_upstream`, the forward URI had no free slot in time, maps to `503
+    /// Service Unavailable`.
+    UpstreamBusy(String),
+    /// The backend's response headers exceeded the configured `max_response_header_bytes`, maps
+    /// to `502 Bad Gateway`.
+    ResponseHeadersTooLarge,
+    /// The request target was `*` with a method other than `OPTIONS`, maps to `400 Bad Request`.
+    AsteriskForm(Method),
+}
+
+impl std::fmt::Display for ProxyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProxyError::InvalidUri(err) => write!(f, "invalid forward uri: {}", err),
+            ProxyError::HyperError(err) => write!(f, "proxying failed: {}", err),
+            ProxyError::ConnectError { uri, source } => {
+                write!(f, "failed to connect to {}: {}", uri, source)
+            }
+            ProxyError::Timeout { uri, source } => {
+                write!(f, "request to {} timed out: {}", uri, source)
+            }
+            ProxyError::ForwardHeaderError => f.write_str("invalid forwarding header value"),
+            ProxyError::UpgradeError(message) => write!(f, "upgrade failed: {}", message),
+            ProxyError::UpstreamUnavailable(err) => write!(f, "upstream unavailable: {}", err),
+            ProxyError::RequestBodyTooLarge => f.write_str("request body too large"),
+            ProxyError::InvalidPath => f.write_str("request path escapes the forward uri"),
+            ProxyError::Cancelled => f.write_str("call was cancelled"),
+            ProxyError::ResponseBodyTooLarge => f.write_str("response body too large"),
+            ProxyError::DisallowedScheme(scheme) => {
+                write!(f, "forwarding to scheme {:?} is not allowed", scheme)
+            }
+            ProxyError::InvalidForwardUri { uri, reason } => {
+                write!(f, "invalid forward uri {:?}: {}", uri, reason)
+            }
+            ProxyError::BuildError(err) => write!(f, "building http message failed: {}", err),
+            ProxyError::ForbiddenAddress(ip) => {
+                write!(f, "forwarding to private address {} is not allowed", ip)
+            }
+            ProxyError::RequestBodyTimeout => f.write_str("request body read timed out"),
+            ProxyError::DuplicateHost => f.write_str("request has multiple host headers"),
+            ProxyError::NoUpstream => f.write_str("no upstream with a positive weight"),
+            ProxyError::CircuitOpen(uri) => write!(f, "circuit for {} is open", uri),
+            ProxyError::UpstreamBusy(uri) => write!(f, "too many concurrent calls to {}", uri),
+            ProxyError::ResponseHeadersTooLarge => f.write_str("response headers too large"),
+            ProxyError::AsteriskForm(method) => {
+                write!(f, "request target * is not allowed for {}", method)
+            }
+        }
+    }
+}
+
+impl ProxyError {
+    /// The status to answer the client with.
+    ///
+    /// Failures reaching the backend map to `502 Bad Gateway`, `503 Service Unavailable` or `504
+    /// Gateway Timeout`, invalid client requests to `4xx` and misconfigurations of the proxy to
+    /// `500 Internal Server Error`.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ProxyError::InvalidUri(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ProxyError::HyperError(_) => StatusCode::BAD_GATEWAY,
+            ProxyError::ConnectError { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            ProxyError::Timeout { .. } => StatusCode::GATEWAY_TIMEOUT,
+            ProxyError::ForwardHeaderError => StatusCode::BAD_REQUEST,
+            ProxyError::UpgradeError(_) => StatusCode::BAD_GATEWAY,
+            ProxyError::UpstreamUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ProxyError::RequestBodyTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            ProxyError::InvalidPath => StatusCode::BAD_REQUEST,
+            ProxyError::Cancelled => StatusCode::SERVICE_UNAVAILABLE,
+            ProxyError::ResponseBodyTooLarge => StatusCode::BAD_GATEWAY,
+            ProxyError::DisallowedScheme(_) => StatusCode::FORBIDDEN,
+            ProxyError::InvalidForwardUri { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+            ProxyError::BuildError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ProxyError::ForbiddenAddress(_) => StatusCode::FORBIDDEN,
+            ProxyError::RequestBodyTimeout => StatusCode::REQUEST_TIMEOUT,
+            ProxyError::DuplicateHost => StatusCode::BAD_REQUEST,
+            ProxyError::NoUpstream => StatusCode::SERVICE_UNAVAILABLE,
+            ProxyError::CircuitOpen(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ProxyError::UpstreamBusy(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ProxyError::ResponseHeadersTooLarge => StatusCode::BAD_GATEWAY,
+            ProxyError::AsteriskForm(_) => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    /// Turns the error into a response with its `status` and the status' reason as body, e.g.
+    /// for `proxy.call(..).await.unwrap_or_else(ProxyError::into_response)`.
+    ///
+    /// The error itself is not sent, as it may reveal details about the backend.
+    pub fn into_response(self) -> Response<Body> {
+        let status = self.status();
+        let mut response = Response::new(Body::from(status.canonical_reason().unwrap_or("")));
+        *response.status_mut() = status;
+
+        response
+    }
+}
+
+impl From<ProxyError> for Response<Body> {
+    fn from(err: ProxyError) -> Self {
+        err.into_response()
+    }
+}
+
+impl std::error::Error for ProxyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ProxyError::InvalidUri(err) => Some(err),
+            ProxyError::HyperError(err) => Some(err),
+            ProxyError::ConnectError { source, .. } => Some(source.as_ref()),
+            ProxyError::Timeout { source, .. } => Some(source),
+            ProxyError::UpstreamUnavailable(err) => Some(err.as_ref()),
+            ProxyError::BuildError(err) => Some(err),
+            _ => None,
+        }
+    }
 }
 
 impl From<Error> for ProxyError {
@@ -156,6 +381,12 @@ impl From<Error> for ProxyError {
     }
 }
 
+impl From<hyper::http::Error> for ProxyError {
+    fn from(err: hyper::http::Error) -> ProxyError {
+        ProxyError::BuildError(err)
+    }
+}
+
 impl From<InvalidUri> for ProxyError {
     fn from(err: InvalidUri) -> ProxyError {
         ProxyError::InvalidUri(err)
@@ -174,299 +405,4085 @@ impl From<InvalidHeaderValue> for ProxyError {
     }
 }
 
-fn remove_hop_headers(headers: &mut HeaderMap) {
-    debug!("Removing hop headers");
-
-    for header in &*HOP_HEADERS {
-        headers.remove(header);
-    }
-}
-
-fn get_upgrade_type(headers: &HeaderMap) -> Option<String> {
-    #[allow(clippy::blocks_in_if_conditions)]
-    if headers
-        .get(&*CONNECTION_HEADER)
-        .map(|value| {
-            value
-                .to_str()
-                .unwrap()
-                .split(',')
-                .any(|e| e.trim() == *UPGRADE_HEADER)
-        })
-        .unwrap_or(false)
-    {
-        if let Some(upgrade_value) = headers.get(&*UPGRADE_HEADER) {
-            debug!(
-                "Found upgrade header with value: {}",
-                upgrade_value.to_str().unwrap().to_owned()
-            );
+/// The scheme a request arrived with, as perceived by the caller, e.g. `https` behind a TLS
+/// terminating listener.
+///
+/// Insert it into the request extensions for `ReverseProxy::forward_inbound_scheme`.
+#[derive(Debug, Clone)]
+pub struct InboundScheme(pub Scheme);
 
-            return Some(upgrade_value.to_str().unwrap().to_owned());
-        }
-    }
+/// Marks requests and responses that went through the proxy, see `ReverseProxy::mark_proxied`.
+///
+/// Check for it with `.extensions().get::<ProxiedMarker>()`.
+#[derive(Debug, Clone, Copy)]
+pub struct ProxiedMarker;
 
-    None
+/// Which client request headers are forwarded to the backend, see `ReverseProxy::header_policy`.
+#[derive(Debug, Clone, Default)]
+pub enum HeaderPolicy {
+    /// Forwards every header except the hop-by-hop ones.
+    #[default]
+    StripHopHeaders,
+    /// Forwards only the listed headers, plus `Content-Length` framing the body.
+    Allowlist(HashSet<HeaderName>),
 }
 
-fn remove_connection_headers(headers: &mut HeaderMap) {
-    if headers.get(&*CONNECTION_HEADER).is_some() {
-        debug!("Removing connection headers");
+/// What to do with a client's `X-Forwarded-For` header exceeding
+/// `ReverseProxy::max_forwarded_for_entries`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForwardedForLimit {
+    /// Keeps the most recent entries, dropping the oldest ones.
+    Truncate,
+    /// Fails the call with `ProxyError::ForwardHeaderError`.
+    Reject,
+}
 
-        let value = headers.get(&*CONNECTION_HEADER).cloned().unwrap();
+/// The separator placed between `X-Forwarded-For` entries, see
+/// `ReverseProxy::forwarded_for_separator`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ForwardedForSeparator {
+    /// `, `, the common form.
+    #[default]
+    CommaSpace,
+    /// `,`, for backends not trimming entries.
+    Comma,
+}
 
-        for name in value.to_str().unwrap().split(',') {
-            if !name.trim().is_empty() {
-                headers.remove(name.trim());
-            }
+impl ForwardedForSeparator {
+    fn as_str(self) -> &'static str {
+        match self {
+            ForwardedForSeparator::CommaSpace => ", ",
+            ForwardedForSeparator::Comma => ",",
         }
     }
 }
 
-fn create_proxied_response<B>(mut response: Response<B>) -> Response<B> {
-    info!("Creating proxied response");
+/// Where the proxy takes the client address from, see `ReverseProxy::client_ip_source`.
+///
+/// Sources other than `SocketPeer` fall back to the `client_ip` passed to the call when they
+/// yield no address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ClientIpSource {
+    /// The `client_ip` passed to the call, usually the socket peer.
+    #[default]
+    SocketPeer,
+    /// The left-most forwarded for entry, when `client_ip` is trusted (see
+    /// `ReverseProxy::trusted_proxies`). The original client sets that entry, so it is only as
+    /// trustworthy as the client.
+    LeftmostForwardedFor,
+    /// The right-most address of the forwarded for entries followed by `client_ip` that is not
+    /// one of the `trusted_proxies`, or the left-most one when all are trusted.
+    RightmostUntrustedForwardedFor,
+    /// The source address of the PROXY protocol header in the request's
+    /// [`ProxyProtocolPreamble`] extension.
+    ProxyProtocol,
+}
 
-    remove_hop_headers(response.headers_mut());
-    remove_connection_headers(response.headers_mut());
+/// A record of what the proxy did with a single request, returned by `ReverseProxy::call_traced`.
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct ProxyTrace {
+    /// The URI the request was forwarded to, `None` when it never got that far.
+    pub forward_uri: Option<String>,
+    /// Hop-by-hop and connection-listed headers removed from the request, e.g. for an audit log.
+    ///
+    /// Only names the request carried are listed, connection-listed ones first.
+    pub stripped_request_headers: Vec<HeaderName>,
+    /// Hop-by-hop and connection-listed headers removed from the response.
+    pub stripped_response_headers: Vec<HeaderName>,
+    /// Whether the connection was upgraded, e.g. to a WebSocket.
+    pub upgraded: bool,
+    /// The status of the response returned to the caller.
+    pub status: Option<StatusCode>,
+    /// The local and remote address of the backend connection, when the connector provides
+    /// `HttpInfo` like `HttpConnector` does.
+    ///
+    /// hyper does not tell whether a pooled connection was reused, but two requests sharing the
+    /// same `local_addr` went over the same connection. Requires the `http` feature.
+    #[cfg(feature = "http")]
+    pub connection: Option<HttpInfo>,
+}
 
-    response
+/// Where the time of a single call went, returned by `ReverseProxy::call_timed`.
+///
+/// Both durations are measured from the start of the call, so `request_built` never exceeds
+/// `response_headers`.
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct ProxyTiming {
+    /// Until the proxied request was built, including the `with_gate` check.
+    pub request_built: Duration,
+    /// Until the backend's response headers arrived, including connecting to the backend.
+    pub response_headers: Duration,
+    /// Whether the connection was upgraded, e.g. to a WebSocket.
+    pub upgraded: bool,
 }
 
-fn forward_uri<B>(forward_url: &str, req: &Request<B>) -> String {
-    debug!("Building forward uri");
+/// The TLS client certificate of a connection on which the proxy terminated mTLS, forwarded to
+/// the backend by `ReverseProxy::call_with_client_cert`.
+#[derive(Debug, Clone, Default)]
+pub struct ClientCertInfo {
+    /// The subject distinguished name, e.g. `CN=client,O=Example`.
+    pub subject: String,
+    /// The certificate fingerprint, e.g. the hex encoded SHA-256 of the DER encoding.
+    pub fingerprint: String,
+}
 
-    let split_url = forward_url.split('?').collect::<Vec<&str>>();
+/// Per-call state threaded through `call_with_options` and `proxy_request`.
+#[derive(Default)]
+struct CallState<'a> {
+    trace: Option<&'a mut ProxyTrace>,
+    timing: Option<&'a mut ProxyTiming>,
+    cancel: Option<&'a CancellationToken>,
+    /// The `Host` header to send instead of the one derived from the forward URI.
+    host: Option<&'a str>,
+    client_cert: Option<&'a ClientCertInfo>,
+    /// The gate and the async request filter already passed, e.g. for a cache miss.
+    admitted: bool,
+}
 
-    let mut base_url: &str = split_url.get(0).unwrap_or(&"");
-    let forward_url_query: &str = split_url.get(1).unwrap_or(&"");
+/// Why the request body limiter or read timeout aborted a request body. An aborted `Body::channel` only fails
+/// the client call with a generic error, which `proxy_request` replaces with this one.
+#[derive(Clone, Default)]
+struct BodyAbort(Arc<Mutex<Option<ProxyError>>>);
 
-    let path2 = req.uri().path();
+impl BodyAbort {
+    fn set(&self, err: ProxyError) {
+        let mut reason = self.0.lock().unwrap_or_else(|err| err.into_inner());
+        reason.get_or_insert(err);
+    }
 
-    if base_url.ends_with('/') {
-        let mut path1_chars = base_url.chars();
-        path1_chars.next_back();
+    fn take(&self) -> Option<ProxyError> {
+        self.0.lock().unwrap_or_else(|err| err.into_inner()).take()
+    }
+}
 
-        base_url = path1_chars.as_str();
+fn map_client_error(err: Error, uri: &Uri) -> ProxyError {
+    if err.is_connect() {
+        ProxyError::ConnectError {
+            uri: uri.to_string(),
+            source: Box::new(err),
+        }
+    } else if err.is_timeout() {
+        ProxyError::Timeout {
+            uri: uri.to_string(),
+            source: err,
+        }
+    } else {
+        err.into()
     }
+}
 
-    let total_length = base_url.len()
-        + path2.len()
-        + 1
-        + forward_url_query.len()
-        + req.uri().query().map(|e| e.len()).unwrap_or(0);
+type BodyChunkTransform = Arc<dyn Fn(Bytes) -> Bytes + Send + Sync>;
+type RequestGate = Arc<dyn Fn(&Request<Body>) -> Result<(), Response<Body>> + Send + Sync>;
+type AsyncRequestFilter = Arc<
+    dyn for<'a> Fn(&'a mut Request<Body>) -> BoxFuture<'a, Result<(), ProxyError>> + Send + Sync,
+>;
+type StatusOverride = Arc<dyn Fn(StatusCode) -> Option<Response<Body>> + Send + Sync>;
+type UpgradeCompleteCallback = Arc<dyn Fn(std::io::Result<(u64, u64)>) + Send + Sync>;
 
-    debug!("Creating url with capacity to {}", total_length);
+fn is_disconnect(err: &std::io::Error) -> bool {
+    matches!(
+        err.kind(),
+        std::io::ErrorKind::BrokenPipe
+            | std::io::ErrorKind::ConnectionAborted
+            | std::io::ErrorKind::ConnectionReset
+            | std::io::ErrorKind::NotConnected
+            | std::io::ErrorKind::UnexpectedEof
+    )
+}
 
-    let mut url = String::with_capacity(total_length);
+/// Consecutive connection failures per forward URI, shared by clones of a proxy.
+struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    circuits: Mutex<HashMap<String, Circuit>>,
+}
 
-    url.push_str(base_url);
-    url.push_str(path2);
+#[derive(Default)]
+struct Circuit {
+    failures: u32,
+    opened_at: Option<Instant>,
+}
 
-    if !forward_url_query.is_empty() || req.uri().query().map(|e| !e.is_empty()).unwrap_or(false) {
-        debug!("Adding query parts to url");
-        url.push('?');
-        url.push_str(forward_url_query);
+impl CircuitBreaker {
+    fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold: threshold.max(1),
+            cooldown,
+            circuits: Mutex::new(HashMap::new()),
+        }
+    }
 
-        if forward_url_query.is_empty() {
-            debug!("Using request query");
+    /// Fails while the circuit of `forward_uri` is open. After the cooldown calls pass again
+    /// (half-open), the next result closes or reopens the circuit.
+    fn check(&self, forward_uri: &str) -> Result<(), ProxyError> {
+        let circuits = self.circuits.lock().unwrap_or_else(|err| err.into_inner());
 
-            url.push_str(req.uri().query().unwrap_or(""));
-        } else {
-            debug!("Merging request and forward_url query");
+        match circuits
+            .get(forward_uri)
+            .and_then(|circuit| circuit.opened_at)
+        {
+            Some(opened_at) if opened_at.elapsed() < self.cooldown => {
+                debug!("Circuit for {} is open", forward_uri);
 
-            let request_query_items = req.uri().query().unwrap_or("").split('&').map(|el| {
-                let parts = el.split('=').collect::<Vec<&str>>();
-                (parts[0], if parts.len() > 1 { parts[1] } else { "" })
-            });
+                Err(ProxyError::CircuitOpen(forward_uri.to_string()))
+            }
+            _ => Ok(()),
+        }
+    }
 
-            let forward_query_items = forward_url_query
-                .split('&')
-                .map(|el| {
-                    let parts = el.split('=').collect::<Vec<&str>>();
-                    parts[0]
-                })
-                .collect::<Vec<_>>();
+    fn record<R>(&self, forward_uri: &str, result: &Result<R, ProxyError>) {
+        let mut circuits = self.circuits.lock().unwrap_or_else(|err| err.into_inner());
 
-            for (key, value) in request_query_items {
-                if !forward_query_items.iter().any(|e| e == &key) {
-                    url.push('&');
-                    url.push_str(key);
-                    url.push('=');
-                    url.push_str(value);
-                }
-            }
+        match result {
+            Err(ProxyError::ConnectError { .. }) => {
+                let circuit = circuits.entry(forward_uri.to_string()).or_default();
+                circuit.failures += 1;
 
-            if url.ends_with('&') {
-                let mut parts = url.chars();
-                parts.next_back();
+                if circuit.failures >= self.threshold {
+                    debug!(
+                        "Opening circuit for {} after {} failures",
+                        forward_uri, circuit.failures
+                    );
 
-                url = parts.as_str().to_string();
+                    circuit.opened_at = Some(Instant::now());
+                }
+            }
+            Err(_) => {}
+            Ok(_) => {
+                circuits.remove(forward_uri);
             }
         }
     }
+}
 
-    debug!("Built forwarding url from request: {}", url);
+/// One semaphore per upstream, shared by clones of a proxy. Semaphores nobody holds a slot of or
+/// waits for are dropped, so forward URIs built from request data do not pile up.
+struct UpstreamLimits {
+    max: usize,
+    acquire_timeout: Option<Duration>,
+    semaphores: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
 
-    url.parse().unwrap()
+/// The scheme and authority of `forward_uri`, which tell its backend apart independent of the
+/// path. A bare authority, e.g. of a tunneled `CONNECT`, is its own key.
+fn upstream_key(forward_uri: &str) -> String {
+    match forward_uri.parse::<Uri>() {
+        Ok(uri) => match (uri.scheme_str(), uri.authority()) {
+            (Some(scheme), Some(authority)) => format!("{}://{}", scheme, authority),
+            (None, Some(authority)) => authority.to_string(),
+            _ => forward_uri.to_string(),
+        },
+        Err(_) => forward_uri.to_string(),
+    }
 }
 
-fn create_proxied_request<B>(
-    client_ip: IpAddr,
-    forward_url: &str,
-    mut request: Request<B>,
-    upgrade_type: Option<&String>,
-) -> Result<Request<B>, ProxyError> {
-    info!("Creating proxied request");
+impl UpstreamLimits {
+    fn new(max: usize, acquire_timeout: Option<Duration>) -> Self {
+        Self {
+            max,
+            acquire_timeout,
+            semaphores: Mutex::new(HashMap::new()),
+        }
+    }
 
-    let contains_te_trailers_value = request
-        .headers()
-        .get(&*TE_HEADER)
-        .map(|value| {
-            value
-                .to_str()
-                .unwrap()
-                .split(',')
-                .any(|e| e.trim() == *TRAILERS_HEADER)
-        })
-        .unwrap_or(false);
+    async fn acquire(&self, forward_uri: &str) -> Result<OwnedSemaphorePermit, ProxyError> {
+        let semaphore = {
+            let mut semaphores = self
+                .semaphores
+                .lock()
+                .unwrap_or_else(|err| err.into_inner());
+            // held permits and waiting calls keep a reference to their semaphore
+            semaphores.retain(|_, semaphore| Arc::strong_count(semaphore) > 1);
 
-    let uri: hyper::Uri = forward_uri(forward_url, &request).parse()?;
+            semaphores
+                .entry(upstream_key(forward_uri))
+                .or_insert_with(|| Arc::new(Semaphore::new(self.max)))
+                .clone()
+        };
+        let busy = || {
+            debug!("No free slot for {}", forward_uri);
 
-    debug!("Setting headers of proxied request");
+            ProxyError::UpstreamBusy(forward_uri.to_string())
+        };
 
-    // remove the original HOST header. It will be set by the client that sends the request: https://github.com/hyperium/hyper/blob/4fcfe1f4ba461209483dec960e36293459a1c60a/src/client/client.rs#L250
-    let original_host = request.headers_mut().remove(HOST);
+        match self.acquire_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, semaphore.acquire_owned())
+                .await
+                .map_err(|_| busy())?
+                .map_err(|_| busy()),
+            None => semaphore.try_acquire_owned().map_err(|_| busy()),
+        }
+    }
+}
 
-    *request.uri_mut() = uri;
+/// How long `block_private_addresses` reuses the checked addresses of a host name.
+const RESOLVED_ADDRESS_TTL: Duration = Duration::from_secs(30);
 
-    remove_hop_headers(request.headers_mut());
-    remove_connection_headers(request.headers_mut());
+/// The addresses host names resolved to for `block_private_addresses`, shared by clones of a
+/// proxy.
+#[derive(Default)]
+struct ResolvedAddresses {
+    resolved: Mutex<HashMap<String, (Instant, Vec<SocketAddr>)>>,
+}
 
-    if contains_te_trailers_value {
-        debug!("Setting up trailer headers");
+impl ResolvedAddresses {
+    /// Resolves `authority`, a `host:port`, or returns its addresses from less than
+    /// `RESOLVED_ADDRESS_TTL` ago.
+    async fn resolve(&self, authority: &str) -> std::io::Result<Vec<SocketAddr>> {
+        {
+            let resolved = self.resolved.lock().unwrap_or_else(|err| err.into_inner());
 
-        request
-            .headers_mut()
-            .insert(&*TE_HEADER, HeaderValue::from_static("trailers"));
-    }
+            if let Some((at, addresses)) = resolved.get(authority) {
+                if at.elapsed() < RESOLVED_ADDRESS_TTL {
+                    return Ok(addresses.clone());
+                }
+            }
+        }
 
-    if let Some(value) = upgrade_type {
-        debug!("Repopulate upgrade headers");
+        let addresses: Vec<SocketAddr> = tokio::net::lookup_host(authority).await?.collect();
+        let mut resolved = self.resolved.lock().unwrap_or_else(|err| err.into_inner());
+        resolved.retain(|_, (at, _)| at.elapsed() < RESOLVED_ADDRESS_TTL);
+        resolved.insert(authority.to_string(), (Instant::now(), addresses.clone()));
 
-        request
-            .headers_mut()
-            .insert(&*UPGRADE_HEADER, value.parse().unwrap());
-        request
-            .headers_mut()
-            .insert(&*CONNECTION_HEADER, HeaderValue::from_static("UPGRADE"));
+        Ok(addresses)
     }
+}
+
+/// When bytes last flowed through an upgraded session, in either direction.
+struct Activity {
+    started: Instant,
+    last_millis: AtomicU64,
+}
 
-    // Add forwarding information in the headers
-    match request.headers_mut().entry(&*X_FORWARDED_FOR) {
-        hyper::header::Entry::Vacant(entry) => {
-            debug!("X-Fowraded-for header was vacant");
-            entry.insert(client_ip.to_string().parse()?);
+impl Activity {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            last_millis: AtomicU64::new(0),
         }
+    }
+
+    fn touch(&self) {
+        let millis = self.started.elapsed().as_millis() as u64;
+        self.last_millis.store(millis, Ordering::Relaxed);
+    }
 
-        hyper::header::Entry::Occupied(entry) => {
-            debug!("X-Fowraded-for header was occupied");
-            let client_ip_str = client_ip.to_string();
-            let mut addr =
-                String::with_capacity(entry.get().as_bytes().len() + 2 + client_ip_str.len());
+    /// Resolves once no bytes flowed for `timeout`.
+    async fn idle(&self, timeout: Duration) {
+        loop {
+            let last_millis = self.last_millis.load(Ordering::Relaxed);
+            let deadline = self.started + Duration::from_millis(last_millis) + timeout;
+            tokio::time::sleep(deadline.saturating_duration_since(Instant::now())).await;
 
-            addr.push_str(std::str::from_utf8(entry.get().as_bytes()).unwrap());
-            addr.push(',');
-            addr.push(' ');
-            addr.push_str(&client_ip_str);
+            if self.last_millis.load(Ordering::Relaxed) == last_millis {
+                return;
+            }
         }
     }
+}
 
-    debug!("Created proxied request");
+const DEFAULT_UPGRADE_BUFFER_SIZE: usize = 8 * 1024;
+const MIN_UPGRADE_BUFFER_SIZE: usize = 1024;
 
-    Ok(request)
+/// Copies `reader` into `writer` until EOF, then shuts `writer` down to pass the half-close on.
+/// The peer going away ends the copy like an EOF.
+async fn copy_half<R, W>(
+    mut reader: R,
+    mut writer: W,
+    activity: &Activity,
+    buffer_size: usize,
+) -> std::io::Result<u64>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut buf = vec![0; buffer_size];
+    let mut copied = 0;
+
+    loop {
+        let read = match reader.read(&mut buf).await {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if is_disconnect(&err) => break,
+            Err(err) => return Err(err),
+        };
+        activity.touch();
+
+        match writer.write_all(&buf[..read]).await {
+            Ok(()) => copied += read as u64,
+            Err(err) if is_disconnect(&err) => return Ok(copied),
+            Err(err) => return Err(err),
+        }
+    }
+
+    match writer.shutdown().await {
+        Err(err) if !is_disconnect(&err) => Err(err),
+        _ => Ok(copied),
+    }
 }
 
-pub async fn call<'a, T: hyper::client::connect::Connect + Clone + Send + Sync + 'static>(
-    client_ip: IpAddr,
-    forward_uri: &str,
-    mut request: Request<Body>,
-    client: &'a Client<T>,
-) -> Result<Response<Body>, ProxyError> {
-    info!(
-        "Received proxy call from {} to {}, client: {}",
-        request.uri().to_string(),
-        forward_uri,
-        client_ip
-    );
+/// Copies between an upgraded backend and client connection in both directions, returning the
+/// bytes sent to the client and to the backend.
+///
+/// Each direction uses a buffer of `buffer_size` bytes. With an `idle_timeout`, both connections
+/// are dropped once no bytes flowed for that long, which
+/// fails the copy with `TimedOut`.
+async fn copy_upgraded<B, C>(
+    backend: B,
+    client: C,
+    idle_timeout: Option<Duration>,
+    buffer_size: usize,
+) -> std::io::Result<(u64, u64)>
+where
+    B: AsyncRead + AsyncWrite,
+    C: AsyncRead + AsyncWrite,
+{
+    let (backend_read, backend_write) = tokio::io::split(backend);
+    let (client_read, client_write) = tokio::io::split(client);
+    let activity = Activity::new();
 
-    let request_upgrade_type = get_upgrade_type(request.headers());
-    let request_upgraded = request.extensions_mut().remove::<OnUpgrade>();
+    let copy = futures_util::future::try_join(
+        copy_half(backend_read, client_write, &activity, buffer_size),
+        copy_half(client_read, backend_write, &activity, buffer_size),
+    );
 
-    let proxied_request = create_proxied_request(
-        client_ip,
-        forward_uri,
-        request,
-        request_upgrade_type.as_ref(),
-    )?;
-    let mut response = client.request(proxied_request).await?;
+    match idle_timeout {
+        Some(timeout) => {
+            futures_util::pin_mut!(copy);
+            let idle = activity.idle(timeout);
+            futures_util::pin_mut!(idle);
 
-    if response.status() == StatusCode::SWITCHING_PROTOCOLS {
-        let response_upgrade_type = get_upgrade_type(response.headers());
+            match futures_util::future::select(copy, idle).await {
+                futures_util::future::Either::Left((result, _)) => result,
+                futures_util::future::Either::Right(_) => Err(std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    "upgraded connection was idle",
+                )),
+            }
+        }
+        None => copy.await,
+    }
+}
 
-        if request_upgrade_type == response_upgrade_type {
-            if let Some(request_upgraded) = request_upgraded {
-                let mut response_upgraded = response
-                    .extensions_mut()
-                    .remove::<OnUpgrade>()
-                    .expect("response does not have an upgrade extension")
-                    .await?;
+/// The upgraded sessions still copying, shared by clones of a proxy.
+#[derive(Default)]
+struct ActiveUpgrades {
+    count: AtomicUsize,
+    finished: Notify,
+}
 
-                debug!("Responding to a connection upgrade response");
+impl ActiveUpgrades {
+    fn start(self: &Arc<Self>) -> ActiveUpgrade {
+        self.count.fetch_add(1, Ordering::SeqCst);
 
-                tokio::spawn(async move {
-                    let mut request_upgraded =
-                        request_upgraded.await.expect("failed to upgrade request");
+        ActiveUpgrade(self.clone())
+    }
 
-                    copy_bidirectional(&mut response_upgraded, &mut request_upgraded)
-                        .await
-                        .expect("coping between upgraded connections failed");
-                });
+    async fn drain(&self) {
+        loop {
+            // registered before the check, so a session finishing in between still wakes it
+            let finished = self.finished.notified();
 
-                Ok(response)
-            } else {
-                Err(ProxyError::UpgradeError(
-                    "request does not have an upgrade extension".to_string(),
-                ))
+            if self.count.load(Ordering::SeqCst) == 0 {
+                return;
             }
-        } else {
-            Err(ProxyError::UpgradeError(format!(
-                "backend tried to switch to protocol {:?} when {:?} was requested",
-                response_upgrade_type, request_upgrade_type
-            )))
+
+            finished.await;
         }
-    } else {
-        let proxied_response = create_proxied_response(response);
+    }
+}
 
-        debug!("Responding to call with response");
-        Ok(proxied_response)
+/// Counts an upgraded session as active until dropped.
+struct ActiveUpgrade(Arc<ActiveUpgrades>);
+
+impl Drop for ActiveUpgrade {
+    fn drop(&mut self) {
+        self.0.count.fetch_sub(1, Ordering::SeqCst);
+        self.0.finished.notify_waiters();
     }
 }
 
-pub struct ReverseProxy<T: hyper::client::connect::Connect + Clone + Send + Sync + 'static> {
-    client: Client<T>,
+/// Per-proxy settings applied while creating proxied requests and responses.
+#[derive(Clone, Default)]
+struct ProxyOptions {
+    gate: Option<RequestGate>,
+    async_request_filter: Option<AsyncRequestFilter>,
+    tunnel_connect: bool,
+    last_upstream_status: Option<Arc<AtomicU16>>,
+    circuit_breaker: Option<Arc<CircuitBreaker>>,
+    upstream_limits: Option<Arc<UpstreamLimits>>,
+    block_private_addresses: Option<Arc<ResolvedAddresses>>,
+    mark_proxied: bool,
+    forward_proxy_auth: bool,
+    normalize_encoding: bool,
+    normalize_path: bool,
+    forward_absolute_form: bool,
+    allowed_schemes: Option<Vec<String>>,
+    force_version: Option<Version>,
+    cookie_domain_rewrite: Option<(String, String)>,
+    stripped_request_cookies: Option<Vec<String>>,
+    header_policy: HeaderPolicy,
+    forward_inbound_scheme: bool,
+    unavailable_retry_after: Option<Duration>,
+    max_request_body: Option<u64>,
+    max_response_header_bytes: Option<usize>,
+    request_body_read_timeout: Option<Duration>,
+    stripped_body_methods: Option<Vec<Method>>,
+    forwarded_for_header: Option<HeaderName>,
+    disable_forwarded_for: bool,
+    collapse_forwarded_for: bool,
+    max_forwarded_for_entries: Option<(usize, ForwardedForLimit)>,
+    forwarded_for_separator: ForwardedForSeparator,
+    client_ip_source: ClientIpSource,
+    trusted_proxies: Option<Vec<IpNet>>,
+    via_pseudonym: Option<String>,
+    server_header: Option<(String, bool)>,
+    stripped_response_headers: Vec<HeaderName>,
+    x_real_ip: bool,
+    x_forwarded_proto: bool,
+    x_forwarded_host: bool,
+    // `Some(None)` strips the header, `None` forwards the client's
+    user_agent: Option<Option<String>>,
+    upstream_authorization: Option<HeaderValue>,
+    client_cert_headers: Option<(HeaderName, HeaderName)>,
+    status_override: Option<StatusOverride>,
+    response_body_map: Option<BodyChunkTransform>,
+    blocking_response_transform: Option<BodyChunkTransform>,
+    body_transform: Option<Arc<dyn BodyTransform>>,
+    on_upgrade_complete: Option<UpgradeCompleteCallback>,
+    active_upgrades: Arc<ActiveUpgrades>,
+    upgrade_idle_timeout: Option<Duration>,
+    upgrade_buffer_size: Option<usize>,
+    upgrade_mismatch_response: bool,
+    follow_internal_redirects: Option<usize>,
+    #[cfg(feature = "signing")]
+    request_signer: Option<HmacSigner>,
+    #[cfg(feature = "metrics")]
+    metrics: Option<Arc<dyn ProxyMetrics>>,
+    #[cfg(feature = "cache")]
+    response_cache: Option<Arc<dyn ResponseCache>>,
+    #[cfg(feature = "regex")]
+    path_rewrite: Option<(Regex, String)>,
 }
 
-impl<T: hyper::client::connect::Connect + Clone + Send + Sync + 'static> ReverseProxy<T> {
-    pub fn new(client: Client<T>) -> Self {
-        Self { client }
+/// The `HOP_HEADERS` removed by `remove_hop_headers`, one bit per entry.
+#[derive(Clone, Copy)]
+struct RemovedHopHeaders(u16);
+
+impl RemovedHopHeaders {
+    fn names(self) -> impl Iterator<Item = &'static HeaderName> {
+        HOP_HEADERS
+            .iter()
+            .enumerate()
+            .filter(move |(index, _)| self.0 & (1 << index) != 0)
+            .map(|(_, name)| name)
     }
+}
 
-    pub async fn call(
-        &self,
-        client_ip: IpAddr,
-        forward_uri: &str,
-        request: Request<Body>,
-    ) -> Result<Response<Body>, ProxyError> {
-        call::<T>(client_ip, forward_uri, request, &self.client).await
+const SINGLE_PASS_MAX_HEADERS: usize = 16;
+
+fn remove_hop_headers(headers: &mut HeaderMap) -> RemovedHopHeaders {
+    debug!("Removing hop headers");
+
+    let mut found = 0;
+
+    // for small maps a single pass finding the hop headers present is cheaper than searching the
+    // map for every hop header, see the `remove hop headers` benchmarks
+    if headers.keys_len() <= SINGLE_PASS_MAX_HEADERS {
+        for name in headers.keys() {
+            if let Some(index) = HOP_HEADERS.iter().position(|header| header == name) {
+                found |= 1 << index;
+            }
+        }
+
+        for name in RemovedHopHeaders(found).names() {
+            headers.remove(name);
+        }
+    } else {
+        for (index, header) in HOP_HEADERS.iter().enumerate() {
+            if headers.remove(header).is_some() {
+                found |= 1 << index;
+            }
+        }
+    }
+
+    RemovedHopHeaders(found)
+}
+
+/// Removes the hop headers like `remove_hop_headers`, except for `kept` when it is given.
+fn remove_hop_headers_except(
+    headers: &mut HeaderMap,
+    kept: Option<&HeaderName>,
+) -> RemovedHopHeaders {
+    let kept = kept.map(|name| {
+        let values: Vec<HeaderValue> = headers.get_all(name).iter().cloned().collect();
+        (name, values)
+    });
+    let removed = remove_hop_headers(headers);
+
+    match kept {
+        Some((name, values)) => {
+            for value in values {
+                headers.append(name, value);
+            }
+
+            let index = HOP_HEADERS.iter().position(|header| header == name);
+            RemovedHopHeaders(index.map_or(removed.0, |index| removed.0 & !(1 << index)))
+        }
+        None => removed,
+    }
+}
+
+fn get_upgrade_type(headers: &HeaderMap) -> Option<String> {
+    let connection_upgrade = headers
+        .get(&*CONNECTION_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.split(',').any(|e| e.trim() == *UPGRADE_HEADER))
+        .unwrap_or(false);
+
+    if connection_upgrade {
+        if let Some(upgrade_value) = headers.get(&*UPGRADE_HEADER) {
+            let upgrade_type = match upgrade_value.to_str() {
+                Ok(upgrade_type) => upgrade_type,
+                Err(_) => {
+                    warn!("Ignoring non-ASCII upgrade header {:?}", upgrade_value);
+
+                    return None;
+                }
+            };
+
+            debug!("Found upgrade header with value: {}", upgrade_type);
+
+            return Some(upgrade_type.to_owned());
+        }
+    }
+
+    None
+}
+
+/// Whether `response` starts a connection upgrade, which the proxy answers by copying between the
+/// upgraded client and backend connections instead of forwarding a body.
+///
+/// ```
+/// use hyper::{Body, Response, StatusCode};
+/// use hyper_reverse_proxy::is_upgrade_response;
+///
+/// let switching = Response::builder()
+///     .status(StatusCode::SWITCHING_PROTOCOLS)
+///     .body(Body::empty())
+///     .unwrap();
+///
+/// assert!(is_upgrade_response(&switching));
+/// assert!(!is_upgrade_response(&Response::new(Body::empty())));
+/// ```
+pub fn is_upgrade_response<B>(response: &Response<B>) -> bool {
+    response.status() == StatusCode::SWITCHING_PROTOCOLS
+}
+
+fn get_websocket_protocols(headers: &HeaderMap) -> Vec<String> {
+    headers
+        .get_all(&*SEC_WEBSOCKET_PROTOCOL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|protocol| protocol.trim().to_owned())
+        .filter(|protocol| !protocol.is_empty())
+        .collect()
+}
+
+fn check_websocket_protocol(offered: &[String], headers: &HeaderMap) -> Result<(), ProxyError> {
+    let selected = match headers.get(&*SEC_WEBSOCKET_PROTOCOL) {
+        Some(value) => value.to_str()?.trim(),
+        None => return Ok(()),
+    };
+
+    if offered.iter().any(|protocol| protocol == selected) {
+        debug!("Backend selected offered websocket protocol {}", selected);
+
+        Ok(())
+    } else {
+        Err(ProxyError::UpgradeError(format!(
+            "backend selected websocket protocol {:?} when {:?} were offered",
+            selected, offered
+        )))
+    }
+}
+
+fn is_framing_header(name: &HeaderName) -> bool {
+    (name == CONTENT_LENGTH || name == TRANSFER_ENCODING || name == HOST)
+        && !HOP_HEADERS.contains(name)
+}
+
+fn remove_connection_headers(headers: &mut HeaderMap) -> Vec<HeaderName> {
+    let mut removed = Vec::new();
+
+    if headers.get(&*CONNECTION_HEADER).is_some() {
+        debug!("Removing connection headers");
+
+        let value = headers.get(&*CONNECTION_HEADER).cloned().unwrap();
+        let value = match value.to_str() {
+            Ok(value) => value,
+            Err(_) => {
+                // the hop headers are still removed, only the listed ones cannot be known
+                warn!("Ignoring non-ASCII connection header {:?}", value);
+
+                return removed;
+            }
+        };
+
+        for name in value.split(',') {
+            let name = name.trim();
+
+            // directives for the client's connection only, the backend connection is managed by
+            // the client's pool
+            if name.eq_ignore_ascii_case("close") || name.eq_ignore_ascii_case("keep-alive") {
+                continue;
+            }
+
+            if let Ok(name) = HeaderName::from_bytes(name.as_bytes()) {
+                // the peer must not be able to strip the headers framing the message
+                if is_framing_header(&name) {
+                    debug!("Keeping connection-listed framing header {}", name);
+                    continue;
+                }
+
+                if headers.remove(&name).is_some() {
+                    removed.push(name);
+                }
+            }
+        }
+    }
+
+    removed
+}
+
+fn append_via(headers: &mut HeaderMap, pseudonym: &str) -> Result<(), ProxyError> {
+    let value = via_value(headers, pseudonym)?;
+    headers.insert(VIA, value);
+
+    Ok(())
+}
+
+fn via_value(headers: &HeaderMap, pseudonym: &str) -> Result<HeaderValue, ProxyError> {
+    debug!("Appending {} to via header", pseudonym);
+
+    let hop = format!("1.1 {}", pseudonym);
+    let mut via = headers
+        .get_all(VIA)
+        .iter()
+        .map(|value| value.to_str())
+        .collect::<Result<Vec<_>, _>>()?;
+    via.push(&hop);
+
+    Ok(via.join(", ").parse()?)
+}
+
+/// The `X-Forwarded-Proto` and `X-Forwarded-Host` values of `request` as configured: the
+/// [`InboundScheme`] or the scheme of its URI, `http` without either, and its `Host` header or
+/// the authority of its URI, as HTTP/2 requests carry it.
+fn forwarded_origin<B>(
+    request: &Request<B>,
+    options: &ProxyOptions,
+) -> (Option<HeaderValue>, Option<HeaderValue>) {
+    let proto = if options.x_forwarded_proto {
+        let scheme = match request.extensions().get::<InboundScheme>() {
+            Some(InboundScheme(scheme)) => scheme.as_str(),
+            None => request.uri().scheme_str().unwrap_or("http"),
+        };
+
+        HeaderValue::from_str(scheme).ok()
+    } else {
+        None
+    };
+    let host = if options.x_forwarded_host {
+        request.headers().get(HOST).cloned().or_else(|| {
+            let authority = request.uri().authority()?;
+
+            HeaderValue::from_str(authority.as_str()).ok()
+        })
+    } else {
+        None
+    };
+
+    (proto, host)
+}
+
+/// Sets the forwarded for header, `X-Forwarded-Proto`, `X-Forwarded-Host`, `X-Real-IP` and `Via`
+/// as configured, always in that order after all other headers, as some backends parse them
+/// positionally.
+///
+/// The forwarded for header is trusted and appended to based on the socket peer `client_ip`,
+/// `X-Real-IP` is the `real_ip` resolved from the `client_ip_source`. `origin` holds the values
+/// of `forwarded_origin`.
+fn apply_forwarded_headers(
+    headers: &mut HeaderMap,
+    client_ip: IpAddr,
+    real_ip: IpAddr,
+    origin: (Option<HeaderValue>, Option<HeaderValue>),
+    options: &ProxyOptions,
+) -> Result<(), ProxyError> {
+    let (proto, host) = origin;
+    let forwarded_for_header = options
+        .forwarded_for_header
+        .as_ref()
+        .unwrap_or(&*X_FORWARDED_FOR);
+    let forwarded_for = forwarded_for_value(headers, forwarded_for_header, client_ip, options)?;
+    let real_ip = if options.x_real_ip {
+        Some(real_ip.to_string().parse()?)
+    } else {
+        None
+    };
+    let via = match &options.via_pseudonym {
+        Some(pseudonym) => Some(via_value(headers, pseudonym)?),
+        None => None,
+    };
+
+    // removing first moves other entries around, inserting afterwards appends in a fixed order
+    let values = [
+        (forwarded_for_header, forwarded_for, true),
+        (&*X_FORWARDED_PROTO, proto, options.x_forwarded_proto),
+        (&*X_FORWARDED_HOST, host, options.x_forwarded_host),
+        (&*X_REAL_IP, real_ip, options.x_real_ip),
+        (&VIA, via, options.via_pseudonym.is_some()),
+    ];
+    for (name, _, replaced) in &values {
+        if *replaced {
+            headers.remove(*name);
+        }
+    }
+    for (name, value, _) in values {
+        if let Some(value) = value {
+            headers.insert(name, value);
+        }
+    }
+
+    Ok(())
+}
+
+/// Appends `client_ip` to the `existing` forwarded for list, separated by `sep`, or starts a new
+/// list when there is none.
+///
+/// ```
+/// use hyper::header::HeaderValue;
+/// use hyper_reverse_proxy::build_forwarded_for;
+///
+/// let existing = HeaderValue::from_static("192.0.2.10");
+/// let value = build_forwarded_for(Some(&existing), "2001:db8::1".parse().unwrap(), ", ");
+///
+/// assert_eq!(value, "192.0.2.10, 2001:db8::1");
+/// ```
+///
+/// # Panics
+///
+/// When `sep` contains characters not allowed in header values, like newlines.
+pub fn build_forwarded_for(
+    existing: Option<&HeaderValue>,
+    client_ip: IpAddr,
+    sep: &str,
+) -> HeaderValue {
+    let client_ip = client_ip.to_string();
+    let value = match existing.filter(|existing| !existing.is_empty()) {
+        Some(existing) => {
+            let mut value = Vec::with_capacity(existing.len() + sep.len() + client_ip.len());
+            value.extend_from_slice(existing.as_bytes());
+            value.extend_from_slice(sep.as_bytes());
+            value.extend_from_slice(client_ip.as_bytes());
+            value
+        }
+        None => client_ip.into_bytes(),
+    };
+
+    HeaderValue::from_bytes(&value).expect("separator is a valid header value")
+}
+
+/// The right-most address of the chain `existing`, `client_ip` that is not a trusted proxy, or
+/// the left-most one when all are trusted.
+fn collapse_forwarded_for(
+    existing: &[&HeaderValue],
+    client_ip: IpAddr,
+    options: &ProxyOptions,
+) -> Result<HeaderValue, ProxyError> {
+    // without `trusted_proxies` the chain is kept, but nobody vouches for it
+    if !is_trusted_proxy(&client_ip, options) {
+        debug!("Collapsing forwarded for header to untrusted {}", client_ip);
+
+        return Ok(client_ip.to_string().parse()?);
+    }
+
+    let entries = forwarded_for_entries(existing)?;
+
+    let client = entries
+        .iter()
+        .rev()
+        // an entry that is no address, e.g. `unknown`, cannot be a trusted proxy either
+        .find(|entry| !matches!(entry.parse::<IpAddr>(), Ok(ip) if is_trusted_proxy(&ip, options)))
+        .or_else(|| entries.first());
+
+    debug!("Collapsing forwarded for header to {:?}", client);
+
+    match client {
+        Some(client) => Ok(client.parse()?),
+        None => Ok(client_ip.to_string().parse()?),
+    }
+}
+
+/// Whether `ip` is one of the `trusted_proxies`, no address is without them.
+fn is_trusted_proxy(ip: &IpAddr, options: &ProxyOptions) -> bool {
+    match &options.trusted_proxies {
+        Some(trusted_proxies) => trusted_proxies.iter().any(|net| net.contains(ip)),
+        None => false,
+    }
+}
+
+fn forwarded_for_entries<'a>(values: &[&'a HeaderValue]) -> Result<Vec<&'a str>, ProxyError> {
+    let mut entries = Vec::new();
+
+    for value in values {
+        entries.extend(
+            value
+                .to_str()?
+                .split(',')
+                .map(str::trim)
+                .filter(|entry| !entry.is_empty()),
+        );
+    }
+
+    Ok(entries)
+}
+
+/// The client address according to the configured `client_ip_source`.
+fn resolve_client_ip<B>(request: &Request<B>, peer: IpAddr, options: &ProxyOptions) -> IpAddr {
+    let forwarded_for_header = options
+        .forwarded_for_header
+        .as_ref()
+        .unwrap_or(&*X_FORWARDED_FOR);
+    let existing: Vec<&HeaderValue> = request
+        .headers()
+        .get_all(forwarded_for_header)
+        .iter()
+        .collect();
+    // a malformed header yields no entries, like a missing one
+    let entries = || forwarded_for_entries(&existing).unwrap_or_default();
+
+    let resolved = match options.client_ip_source {
+        ClientIpSource::SocketPeer => None,
+        ClientIpSource::LeftmostForwardedFor if is_trusted_proxy(&peer, options) => entries()
+            .first()
+            .and_then(|entry| entry.parse::<IpAddr>().ok()),
+        ClientIpSource::LeftmostForwardedFor => None,
+        ClientIpSource::RightmostUntrustedForwardedFor => {
+            let mut client = peer;
+
+            for entry in entries().iter().rev() {
+                if !is_trusted_proxy(&client, options) {
+                    break;
+                }
+
+                match entry.parse::<IpAddr>() {
+                    Ok(ip) => client = ip,
+                    // an entry that is no address, e.g. `unknown`, ends the chain that is known
+                    Err(_) => break,
+                }
+            }
+
+            Some(client)
+        }
+        ClientIpSource::ProxyProtocol => request
+            .extensions()
+            .get::<ProxyProtocolPreamble>()
+            .and_then(|preamble| client_ip_from_proxy_protocol(&preamble.0)),
+    };
+
+    match resolved {
+        Some(client_ip) => {
+            debug!(
+                "Resolved client ip {} from {:?}",
+                client_ip, options.client_ip_source
+            );
+
+            client_ip
+        }
+        None => peer,
+    }
+}
+
+/// The forwarded for header with `client_ip` appended, `None` when it is disabled.
+fn forwarded_for_value(
+    headers: &HeaderMap,
+    forwarded_for_header: &HeaderName,
+    client_ip: IpAddr,
+    options: &ProxyOptions,
+) -> Result<Option<HeaderValue>, ProxyError> {
+    if options.disable_forwarded_for {
+        debug!("Removing forwarded for header");
+
+        return Ok(None);
+    }
+
+    let mut existing: Vec<&HeaderValue> = headers.get_all(forwarded_for_header).iter().collect();
+
+    if let Some(trusted_proxies) = &options.trusted_proxies {
+        if !trusted_proxies.iter().any(|net| net.contains(&client_ip)) {
+            debug!(
+                "Removing forwarded for header of untrusted client {}",
+                client_ip
+            );
+
+            existing.clear();
+        }
+    }
+
+    if options.collapse_forwarded_for {
+        return Ok(Some(collapse_forwarded_for(&existing, client_ip, options)?));
+    }
+
+    if existing.is_empty() {
+        debug!("X-Fowraded-for header was vacant");
+
+        return Ok(Some(build_forwarded_for(None, client_ip, ", ")));
+    }
+
+    debug!("X-Fowraded-for header was occupied");
+    let separator = options.forwarded_for_separator.as_str();
+
+    let existing: HeaderValue = if options.max_forwarded_for_entries.is_some()
+        || options.forwarded_for_separator != ForwardedForSeparator::CommaSpace
+    {
+        let mut entries = Vec::new();
+        for value in &existing {
+            entries.extend(
+                value
+                    .to_str()?
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|entry| !entry.is_empty()),
+            );
+        }
+
+        if let Some((max, limit)) = options.max_forwarded_for_entries {
+            if entries.len() > max {
+                debug!("Forwarded for header has {} entries", entries.len());
+
+                match limit {
+                    ForwardedForLimit::Reject => return Err(ProxyError::ForwardHeaderError),
+                    ForwardedForLimit::Truncate => {
+                        entries.drain(..entries.len() - max);
+                    }
+                }
+            }
+        }
+
+        entries.join(separator).parse()?
+    } else {
+        // several header lines form one list (RFC 7230 3.2.2), keep all of them in order
+        existing
+            .iter()
+            .map(|value| value.to_str())
+            .collect::<Result<Vec<_>, _>>()?
+            .join(", ")
+            .parse()?
+    };
+
+    Ok(Some(build_forwarded_for(
+        Some(&existing),
+        client_ip,
+        separator,
+    )))
+}
+
+/// Standard base64 with padding (RFC 4648 section 4), as used by basic auth.
+fn base64_encode(input: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(input.len().div_ceil(3) * 4);
+    for chunk in input.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+
+        for index in 0..4 {
+            if index <= chunk.len() {
+                let sextet = (bits >> (18 - 6 * index)) & 0x3f;
+                encoded.push(char::from(ALPHABET[sextet as usize]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+/// Percent-encodes `value` outside printable ASCII, so e.g. a UTF-8 subject or an injected line
+/// break cannot corrupt the header it is sent in.
+fn header_safe_value(value: &str) -> HeaderValue {
+    let mut encoded = String::with_capacity(value.len());
+
+    for byte in value.bytes() {
+        if (b' '..=b'~').contains(&byte) && byte != b'%' {
+            encoded.push(char::from(byte));
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+
+    HeaderValue::from_str(&encoded).expect("printable ASCII is a valid header value")
+}
+
+fn set_client_cert_headers(headers: &mut HeaderMap, cert: &ClientCertInfo, options: &ProxyOptions) {
+    debug!("Setting client certificate headers");
+
+    let (subject_header, fingerprint_header) = match &options.client_cert_headers {
+        Some((subject, fingerprint)) => (subject, fingerprint),
+        None => (&*X_CLIENT_CERT_SUBJECT, &*X_CLIENT_CERT_FINGERPRINT),
+    };
+
+    for (header, value) in [
+        (subject_header, &cert.subject),
+        (fingerprint_header, &cert.fingerprint),
+    ] {
+        if value.is_empty() {
+            headers.remove(header);
+        } else {
+            headers.insert(header, header_safe_value(value));
+        }
+    }
+}
+
+fn set_server_header(
+    headers: &mut HeaderMap,
+    server: &str,
+    overwrite: bool,
+) -> Result<(), ProxyError> {
+    let value = match headers.get(SERVER) {
+        // product tokens are separated by whitespace (RFC 7231 7.4.2)
+        Some(backend) if !overwrite => format!("{} {}", backend.to_str()?, server),
+        _ => server.to_string(),
+    };
+    headers.insert(SERVER, value.parse()?);
+
+    Ok(())
+}
+
+fn forwarded_trailer_names(headers: &HeaderMap) -> Vec<HeaderName> {
+    headers
+        .get_all(&*TRAILER_HEADER)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|name| HeaderName::from_bytes(name.trim().as_bytes()).ok())
+        .filter(|name| !HOP_HEADERS.contains(name))
+        .collect()
+}
+
+fn strip_cookies(headers: &mut HeaderMap, names: &[String]) -> Result<(), ProxyError> {
+    let mut stripped = false;
+    let mut kept = Vec::new();
+
+    // HTTP/2 clients may split the cookies over several headers
+    for value in headers.get_all(COOKIE) {
+        for cookie in value.to_str()?.split(';') {
+            let cookie = cookie.trim();
+            let name = cookie.split('=').next().unwrap_or("").trim();
+
+            if names.iter().any(|stripped| stripped == name) {
+                stripped = true;
+            } else if !cookie.is_empty() {
+                kept.push(cookie);
+            }
+        }
+    }
+
+    if stripped {
+        debug!("Stripping request cookies");
+
+        let kept = if kept.is_empty() {
+            None
+        } else {
+            Some(kept.join("; ").parse()?)
+        };
+
+        match kept {
+            Some(value) => headers.insert(COOKIE, value),
+            None => headers.remove(COOKIE),
+        };
+    }
+
+    Ok(())
+}
+
+/// Rewrites the `Domain` attribute of a `Set-Cookie` value matching `from` to `to`, returns `None`
+/// when the cookie has no such attribute.
+fn rewrite_cookie_domain(cookie: &str, from: &str, to: &str) -> Option<String> {
+    let mut rewritten = false;
+    let attributes = cookie
+        .split(';')
+        .enumerate()
+        .map(|(index, attribute)| {
+            let attribute = attribute.trim();
+
+            // the first pair is the cookie itself, which may well be named domain
+            match attribute.split_once('=') {
+                Some((name, value))
+                    if index > 0
+                        && name.trim().eq_ignore_ascii_case("domain")
+                        && value
+                            .trim()
+                            .trim_start_matches('.')
+                            .eq_ignore_ascii_case(from.trim_start_matches('.')) =>
+                {
+                    rewritten = true;
+                    format!("Domain={}", to)
+                }
+                _ => attribute.to_string(),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if rewritten {
+        Some(attributes.join("; "))
+    } else {
+        None
+    }
+}
+
+fn create_proxied_response<B>(
+    mut response: Response<B>,
+    options: &ProxyOptions,
+    trace: Option<&mut ProxyTrace>,
+) -> Response<B> {
+    info!("Creating proxied response");
+
+    let trailer_names = forwarded_trailer_names(response.headers());
+    let chunked = response.headers().contains_key(TRANSFER_ENCODING);
+
+    // connection-listed headers first, the connection header itself is a hop header
+    let mut stripped = remove_connection_headers(response.headers_mut());
+    let removed_hop_headers = remove_hop_headers_except(
+        response.headers_mut(),
+        Some(&PROXY_AUTHENTICATE).filter(|_| options.forward_proxy_auth),
+    );
+
+    if let Some(trace) = trace {
+        stripped.extend(removed_hop_headers.names().cloned());
+        trace.stripped_response_headers = stripped;
+    }
+
+    for name in &options.stripped_response_headers {
+        if response.headers_mut().remove(name).is_some() {
+            debug!("Stripped response header {}", name);
+        }
+    }
+
+    // transfer-encoding overrides content-length (RFC 7230 3.3.3), once it is stripped a stale
+    // content-length would be used to frame the body towards the client
+    if chunked && response.headers_mut().remove(CONTENT_LENGTH).is_some() {
+        debug!("Removed content-length of a chunked response");
+    }
+
+    // the version is per hop as well. An HTTP/1.0 backend can delimit the body by closing the
+    // connection, answering as HTTP/1.1 lets hyper chunk it instead, while it still downgrades
+    // the response for HTTP/1.0 clients
+    if response.version() == Version::HTTP_10 {
+        debug!("Answering HTTP/1.0 response as HTTP/1.1");
+
+        *response.version_mut() = Version::HTTP_11;
+    }
+
+    if !trailer_names.is_empty() {
+        debug!("Announcing forwarded trailers");
+
+        let names = trailer_names
+            .iter()
+            .map(HeaderName::as_str)
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        response
+            .headers_mut()
+            .insert(&*TRAILER_HEADER, HeaderValue::from_str(&names).unwrap());
+    }
+
+    if let Some((from, to)) = &options.cookie_domain_rewrite {
+        if let hyper::header::Entry::Occupied(mut cookies) =
+            response.headers_mut().entry(SET_COOKIE)
+        {
+            for cookie in cookies.iter_mut() {
+                let rewritten = cookie
+                    .to_str()
+                    .ok()
+                    .and_then(|value| rewrite_cookie_domain(value, from, to))
+                    .and_then(|value| HeaderValue::from_str(&value).ok());
+
+                if let Some(rewritten) = rewritten {
+                    debug!("Rewrote cookie domain {} to {}", from, to);
+
+                    *cookie = rewritten;
+                }
+            }
+        }
+    }
+
+    response
+}
+
+/// Forwards `body` while dropping trailers named like hop headers, matching the `Trailer`
+/// header announced by `create_proxied_response`.
+fn filter_trailers(mut body: Body) -> Body {
+    let (mut sender, filtered) = Body::channel();
+
+    tokio::spawn(async move {
+        while let Some(chunk) = body.data().await {
+            match chunk {
+                Ok(chunk) => {
+                    if sender.send_data(chunk).await.is_err() {
+                        return;
+                    }
+                }
+                Err(err) => {
+                    debug!("Reading response body failed: {}", err);
+                    sender.abort();
+                    return;
+                }
+            }
+        }
+
+        match body.trailers().await {
+            Ok(Some(mut trailers)) => {
+                debug!("Filtering response trailers");
+
+                remove_hop_headers(&mut trailers);
+                let _ = sender.send_trailers(trailers).await;
+            }
+            Ok(None) => {}
+            Err(err) => {
+                debug!("Reading response trailers failed: {}", err);
+                sender.abort();
+            }
+        }
+    });
+
+    filtered
+}
+
+/// Forwards `body` until `token` is cancelled, then aborts it so the client sees an error instead
+/// of a truncated body.
+fn cancellable_body(mut body: Body, token: CancellationToken) -> Body {
+    let (mut sender, cancellable) = Body::channel();
+
+    tokio::spawn(async move {
+        let forward = async {
+            while let Some(chunk) = body.data().await {
+                match chunk {
+                    Ok(chunk) => {
+                        if sender.send_data(chunk).await.is_err() {
+                            return Ok(());
+                        }
+                    }
+                    Err(err) => {
+                        debug!("Reading response body failed: {}", err);
+                        return Err(());
+                    }
+                }
+            }
+
+            match body.trailers().await {
+                Ok(Some(trailers)) => {
+                    let _ = sender.send_trailers(trailers).await;
+                    Ok(())
+                }
+                Ok(None) => Ok(()),
+                Err(err) => {
+                    debug!("Reading response trailers failed: {}", err);
+                    Err(())
+                }
+            }
+        };
+
+        match token.run_until_cancelled(forward).await {
+            Some(Ok(())) => {}
+            Some(Err(())) => sender.abort(),
+            None => {
+                debug!("Response body cancelled");
+                sender.abort();
+            }
+        }
+    });
+
+    cancellable
+}
+
+/// Forwards `body` with every chunk passed through `map`, keeping its trailers, which
+/// `Body::wrap_stream` would drop.
+fn map_body_chunks<F, Fut>(mut body: Body, map: F) -> Body
+where
+    F: Fn(Bytes) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<Bytes, tokio::task::JoinError>> + Send,
+{
+    let (mut sender, mapped) = Body::channel();
+
+    tokio::spawn(async move {
+        while let Some(chunk) = body.data().await {
+            let chunk = match chunk {
+                Ok(chunk) => map(chunk).await,
+                Err(err) => {
+                    debug!("Reading response body failed: {}", err);
+                    sender.abort();
+                    return;
+                }
+            };
+
+            match chunk {
+                Ok(chunk) => {
+                    if sender.send_data(chunk).await.is_err() {
+                        return;
+                    }
+                }
+                Err(err) => {
+                    debug!("Transforming response body failed: {}", err);
+                    sender.abort();
+                    return;
+                }
+            }
+        }
+
+        match body.trailers().await {
+            Ok(Some(trailers)) => {
+                let _ = sender.send_trailers(trailers).await;
+            }
+            Ok(None) => {}
+            Err(err) => {
+                debug!("Reading response trailers failed: {}", err);
+                sender.abort();
+            }
+        }
+    });
+
+    mapped
+}
+
+fn map_body(body: Body, map: BodyChunkTransform) -> Body {
+    map_body_chunks(body, move |chunk| {
+        futures_util::future::ready(Ok(map(chunk)))
+    })
+}
+
+fn transform_body_blocking(body: Body, transform: BodyChunkTransform) -> Body {
+    map_body_chunks(body, move |chunk| {
+        let transform = transform.clone();
+
+        tokio::task::spawn_blocking(move || transform(chunk))
+    })
+}
+
+/// Passes the body of `message` through `transform`, unless it is known to be empty: turning the
+/// empty body of e.g. a GET request into a stream would make hyper send it chunked.
+fn apply_body_transform<F>(headers: &mut HeaderMap, body: &mut Body, transform: F)
+where
+    F: FnOnce(&mut HeaderMap, Body) -> Body,
+{
+    if body.is_end_stream() {
+        return;
+    }
+
+    debug!("Transforming body");
+
+    // the transform may change the body length
+    headers.remove(CONTENT_LENGTH);
+    *body = transform(headers, std::mem::take(body));
+}
+
+/// Forwards `body` with its trailers until more than `limit` bytes arrived, then aborts it with
+/// `RequestBodyTooLarge` recorded in `abort`.
+fn limit_body(mut body: Body, limit: u64, abort: BodyAbort) -> Body {
+    if body.is_end_stream() {
+        return body;
+    }
+
+    let (mut sender, limited) = Body::channel();
+
+    tokio::spawn(async move {
+        let mut received: u64 = 0;
+
+        while let Some(chunk) = body.data().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(err) => {
+                    debug!("Reading request body failed: {}", err);
+                    sender.abort();
+                    return;
+                }
+            };
+            received += chunk.len() as u64;
+
+            if received > limit {
+                debug!("Request body exceeded limit of {} bytes", limit);
+
+                abort.set(ProxyError::RequestBodyTooLarge);
+                sender.abort();
+                return;
+            }
+
+            if sender.send_data(chunk).await.is_err() {
+                return;
+            }
+        }
+
+        match body.trailers().await {
+            Ok(Some(trailers)) => {
+                let _ = sender.send_trailers(trailers).await;
+            }
+            Ok(None) => {}
+            Err(err) => {
+                debug!("Reading request trailers failed: {}", err);
+                sender.abort();
+            }
+        }
+    });
+
+    limited
+}
+
+/// Forwards `body` with its trailers, failing it with `RequestBodyTimeout` recorded in `abort` once
+/// no data arrived for `timeout`, the time between chunks rather than for the whole body.
+fn timeout_body(mut body: Body, timeout: Duration, abort: BodyAbort) -> Body {
+    if body.is_end_stream() {
+        return body;
+    }
+
+    let (mut sender, timed) = Body::channel();
+
+    tokio::spawn(async move {
+        loop {
+            match tokio::time::timeout(timeout, body.data()).await {
+                Ok(Some(Ok(chunk))) => {
+                    if sender.send_data(chunk).await.is_err() {
+                        return;
+                    }
+                }
+                Ok(Some(Err(err))) => {
+                    debug!("Reading request body failed: {}", err);
+                    sender.abort();
+                    return;
+                }
+                Ok(None) => break,
+                Err(_) => {
+                    debug!("No request body data within {:?}", timeout);
+
+                    abort.set(ProxyError::RequestBodyTimeout);
+                    sender.abort();
+                    return;
+                }
+            }
+        }
+
+        match body.trailers().await {
+            Ok(Some(trailers)) => {
+                let _ = sender.send_trailers(trailers).await;
+            }
+            Ok(None) => {}
+            Err(err) => {
+                debug!("Reading request trailers failed: {}", err);
+                sender.abort();
+            }
+        }
+    });
+
+    timed
+}
+
+async fn buffer_body(mut body: Body, limit: u64) -> Result<Bytes, ProxyError> {
+    if body.size_hint().lower() > limit {
+        debug!("Response body is announced larger than {} bytes", limit);
+
+        return Err(ProxyError::ResponseBodyTooLarge);
+    }
+
+    let mut buffered = Vec::new();
+
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+
+        if (buffered.len() + chunk.len()) as u64 > limit {
+            debug!("Response body exceeded limit of {} bytes", limit);
+
+            return Err(ProxyError::ResponseBodyTooLarge);
+        }
+
+        buffered.extend_from_slice(&chunk);
+    }
+
+    Ok(buffered.into())
+}
+
+/// Collapses empty and `.` segments and resolves `..` segments of `path`, also in their percent
+/// encoded forms. Returns `None` when a `..` would leave the root.
+fn normalize_path(path: &str) -> Option<String> {
+    let mut segments = Vec::new();
+
+    for segment in path.split('/') {
+        let segment_lower = segment.to_ascii_lowercase();
+
+        match segment_lower.as_str() {
+            "" | "." | "%2e" => {}
+            ".." | ".%2e" | "%2e." | "%2e%2e" => {
+                segments.pop()?;
+            }
+            _ => segments.push(segment),
+        }
+    }
+
+    let mut normalized = String::with_capacity(path.len());
+
+    for segment in &segments {
+        normalized.push('/');
+        normalized.push_str(segment);
+    }
+
+    // keep the trailing slash of directory like paths
+    if segments.is_empty() || path.ends_with('/') || path.ends_with("/.") || path.ends_with("/..") {
+        normalized.push('/');
+    }
+
+    Some(normalized)
+}
+
+fn forward_uri<B>(forward_url: &str, req: &Request<B>) -> String {
+    debug!("Building forward uri");
+
+    let split_url = forward_url.split('?').collect::<Vec<&str>>();
+
+    let mut base_url: &str = split_url.first().unwrap_or(&"");
+    let forward_url_query: &str = split_url.get(1).unwrap_or(&"");
+
+    // `OPTIONS *` asks about the server rather than a resource. hyper's client cannot send the
+    // asterisk-form, so it is forwarded as an `OPTIONS` of the forward URL, like an empty path
+    let path2 = match req.uri().path() {
+        "*" => "",
+        path => path,
+    };
+
+    if base_url.ends_with('/') {
+        let mut path1_chars = base_url.chars();
+        path1_chars.next_back();
+
+        base_url = path1_chars.as_str();
+    }
+
+    let total_length = base_url.len()
+        + path2.len()
+        + 1
+        + forward_url_query.len()
+        + req.uri().query().map(|e| e.len()).unwrap_or(0);
+
+    debug!("Creating url with capacity to {}", total_length);
+
+    let mut url = String::with_capacity(total_length);
+
+    url.push_str(base_url);
+    url.push_str(path2);
+
+    if !forward_url_query.is_empty() || req.uri().query().map(|e| !e.is_empty()).unwrap_or(false) {
+        debug!("Adding query parts to url");
+        url.push('?');
+        url.push_str(forward_url_query);
+
+        if forward_url_query.is_empty() {
+            debug!("Using request query");
+
+            url.push_str(req.uri().query().unwrap_or(""));
+        } else {
+            debug!("Merging request and forward_url query");
+
+            // a missing request query splits into one empty item, which must not add a stray `&=`
+            let request_query_items = req
+                .uri()
+                .query()
+                .unwrap_or("")
+                .split('&')
+                .filter(|el| !el.is_empty())
+                .map(|el| {
+                    let parts = el.split('=').collect::<Vec<&str>>();
+                    (parts[0], if parts.len() > 1 { parts[1] } else { "" })
+                });
+
+            let forward_query_items = forward_url_query
+                .split('&')
+                .map(|el| {
+                    let parts = el.split('=').collect::<Vec<&str>>();
+                    parts[0]
+                })
+                .collect::<Vec<_>>();
+
+            for (key, value) in request_query_items {
+                if !forward_query_items.iter().any(|e| e == &key) {
+                    url.push('&');
+                    url.push_str(key);
+                    url.push('=');
+                    url.push_str(value);
+                }
+            }
+
+            if url.ends_with('&') {
+                let mut parts = url.chars();
+                parts.next_back();
+
+                url = parts.as_str().to_string();
+            }
+        }
+    }
+
+    debug!("Built forwarding url from request: {}", url);
+
+    url.parse().unwrap()
+}
+
+/// Computes the URI `req` is forwarded to by a call with `forward_url`, without performing it.
+///
+/// The request path is appended to the path of `forward_url`. Query parameters of `forward_url`
+/// come first and win, request query parameters are appended unless `forward_url` already sets
+/// the same key.
+///
+/// ```
+/// use hyper::{Body, Request};
+/// use hyper_reverse_proxy::build_forward_uri;
+///
+/// let request = Request::get("/users?page=2").body(Body::empty()).unwrap();
+///
+/// assert_eq!(
+///     build_forward_uri("http://backend:8080", &request).unwrap(),
+///     "http://backend:8080/users?page=2"
+/// );
+/// assert_eq!(
+///     build_forward_uri("http://backend/api/", &request).unwrap(),
+///     "http://backend/api/users?page=2"
+/// );
+/// assert_eq!(
+///     build_forward_uri("http://backend?page=1&debug=true", &request).unwrap(),
+///     "http://backend/users?page=1&debug=true"
+/// );
+///
+/// let request = Request::get("/users?page=2&sort=name").body(Body::empty()).unwrap();
+///
+/// assert_eq!(
+///     build_forward_uri("http://backend?page=1", &request).unwrap(),
+///     "http://backend/users?page=1&sort=name"
+/// );
+/// assert!(build_forward_uri("not a uri", &request).is_err());
+/// ```
+pub fn build_forward_uri<B>(forward_url: &str, req: &Request<B>) -> Result<Uri, ProxyError> {
+    Ok(forward_uri(forward_url, req).parse()?)
+}
+
+/// Whether `ip` is a loopback, link-local, private (RFC 1918, unique local) or unspecified
+/// address.
+fn is_private_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            ip.is_loopback() || ip.is_link_local() || ip.is_private() || ip.is_unspecified()
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_private_address(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+
+                ip.is_loopback()
+                    || ip.is_unspecified()
+                    || first & 0xffc0 == 0xfe80
+                    || first & 0xfe00 == 0xfc00
+            }
+        },
+    }
+}
+
+/// Fails when any of the `addresses` `target` resolved to is private.
+fn check_addresses(
+    target: &dyn std::fmt::Display,
+    addresses: impl IntoIterator<Item = IpAddr>,
+) -> Result<(), ProxyError> {
+    match addresses.into_iter().find(|ip| is_private_address(*ip)) {
+        Some(ip) => {
+            debug!("Rejecting forward uri {} resolving to {}", target, ip);
+
+            Err(ProxyError::ForbiddenAddress(ip))
+        }
+        None => Ok(()),
+    }
+}
+
+/// Resolves the host of `uri` and fails when any of its addresses is private. Returns the
+/// checked address to connect to when the host is a name.
+async fn check_forward_address(
+    uri: &Uri,
+    resolved: &ResolvedAddresses,
+) -> Result<Option<SocketAddr>, ProxyError> {
+    let host = uri.host().unwrap_or("");
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return check_addresses(uri, Some(ip)).map(|_| None);
+    }
+
+    let port = uri
+        .port_u16()
+        .unwrap_or(if uri.scheme() == Some(&Scheme::HTTPS) {
+            443
+        } else {
+            80
+        });
+    let addresses = resolved
+        .resolve(&format!("{}:{}", host, port))
+        .await
+        .map_err(|err| ProxyError::UpstreamUnavailable(Box::new(err)))?;
+
+    check_addresses(uri, addresses.iter().map(|addr| addr.ip()))?;
+
+    Ok(addresses.first().copied())
+}
+
+/// Rejects forward URLs without scheme or authority, which joined with the request path would
+/// turn into a confusing connect error, e.g. `http://` and `/path` into `http://path`.
+fn check_forward_url(forward_url: &str) -> Result<(), ProxyError> {
+    let base = forward_url.split('?').next().unwrap_or("");
+    let reason = match base.split_once("://") {
+        Some(("", _)) | None => "missing scheme",
+        Some((_, rest)) if rest.is_empty() || rest.starts_with('/') => "missing authority",
+        Some(_) => return Ok(()),
+    };
+
+    Err(ProxyError::InvalidForwardUri {
+        uri: forward_url.to_string(),
+        reason,
+    })
+}
+
+/// The URI `request` is forwarded to, after normalizing and rewriting its path as configured.
+fn proxied_uri<B>(
+    forward_url: &str,
+    request: &mut Request<B>,
+    options: &ProxyOptions,
+) -> Result<Uri, ProxyError> {
+    if options.normalize_path && request.uri().path() != "*" {
+        let path = normalize_path(request.uri().path()).ok_or(ProxyError::InvalidPath)?;
+
+        if path != request.uri().path() {
+            debug!("Normalized request path to {}", path);
+
+            let path_and_query = match request.uri().query() {
+                Some(query) => format!("{}?{}", path, query),
+                None => path,
+            };
+            let mut parts = request.uri().clone().into_parts();
+            parts.path_and_query = Some(path_and_query.parse()?);
+            *request.uri_mut() = Uri::from_parts(parts).expect("only the path was changed");
+        }
+    }
+
+    #[cfg(feature = "regex")]
+    if let Some((regex, replacement)) = &options.path_rewrite {
+        if let std::borrow::Cow::Owned(path) = regex.replace(request.uri().path(), replacement) {
+            debug!("Rewrote request path to {}", path);
+
+            let path_and_query = match request.uri().query() {
+                Some(query) => format!("{}?{}", path, query),
+                None => path,
+            };
+            let mut parts = request.uri().clone().into_parts();
+            parts.path_and_query = Some(path_and_query.parse()?);
+            *request.uri_mut() = Uri::from_parts(parts).expect("only the path was changed");
+        }
+    }
+
+    // hyper gives every HTTP/2 request an absolute URI, only HTTP/1 has a distinct absolute-form
+    let http1 = matches!(
+        request.version(),
+        Version::HTTP_09 | Version::HTTP_10 | Version::HTTP_11
+    );
+    let absolute_form_url = match (request.uri().scheme_str(), request.uri().authority()) {
+        (Some(scheme), Some(authority)) if options.forward_absolute_form && http1 => {
+            debug!(
+                "Forwarding to authority of absolute-form request {}",
+                authority
+            );
+
+            Some(format!("{}://{}", scheme, authority))
+        }
+        _ => None,
+    };
+    let forward_url = absolute_form_url.as_deref().unwrap_or(forward_url);
+
+    check_forward_url(forward_url)?;
+
+    let mut uri = build_forward_uri(forward_url, request)?;
+
+    if options.forward_inbound_scheme {
+        if let Some(InboundScheme(scheme)) = request.extensions().get::<InboundScheme>() {
+            debug!("Using inbound scheme {}", scheme);
+
+            let mut parts = uri.into_parts();
+            parts.scheme = Some(scheme.clone());
+            uri = Uri::from_parts(parts).expect("only the scheme was changed");
+        }
+    }
+
+    if let Some(allowed_schemes) = &options.allowed_schemes {
+        let scheme = uri.scheme_str().unwrap_or("");
+
+        if !allowed_schemes
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(scheme))
+        {
+            debug!("Rejecting forward uri with scheme {:?}", scheme);
+
+            return Err(ProxyError::DisallowedScheme(scheme.to_string()));
+        }
+    }
+
+    Ok(uri)
+}
+
+fn create_proxied_request<B>(
+    client_ip: IpAddr,
+    forward_url: &str,
+    mut request: Request<B>,
+    upgrade_type: Option<&String>,
+    options: &ProxyOptions,
+    trace: Option<&mut ProxyTrace>,
+) -> Result<Request<B>, ProxyError> {
+    info!("Creating proxied request");
+
+    // picking one of several hosts could route differently than a server in front of the proxy did
+    if request.headers().get_all(HOST).iter().nth(1).is_some() {
+        debug!("Rejecting request with multiple host headers");
+
+        return Err(ProxyError::DuplicateHost);
+    }
+
+    let real_ip = resolve_client_ip(&request, client_ip, options);
+    let origin = forwarded_origin(&request, options);
+
+    let asterisk_form = request.uri().path() == "*";
+
+    if asterisk_form && request.method() != Method::OPTIONS {
+        debug!(
+            "Rejecting asterisk-form request target for {}",
+            request.method()
+        );
+
+        return Err(ProxyError::AsteriskForm(request.method().clone()));
+    }
+
+    // transfer codings are negotiated per connection and hyper's client does not decode them, so
+    // `trailers` is the only TE token forwarded
+    let contains_te_trailers_value = request
+        .headers()
+        .get_all(&*TE_HEADER)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|token| {
+            let token = token.split(';').next().unwrap_or("").trim();
+            token.eq_ignore_ascii_case(TRAILERS_HEADER.as_str())
+        });
+
+    let uri = proxied_uri(forward_url, &mut request, options)?;
+
+    debug!("Setting headers of proxied request");
+
+    // remove the original HOST header. It will be set by the client that sends the request: https://github.com/hyperium/hyper/blob/4fcfe1f4ba461209483dec960e36293459a1c60a/src/client/client.rs#L250
+    request.headers_mut().remove(HOST);
+
+    // connection-listed headers first, the connection header itself is a hop header
+    let mut stripped = remove_connection_headers(request.headers_mut());
+    let removed_hop_headers = remove_hop_headers_except(
+        request.headers_mut(),
+        Some(&PROXY_AUTHORIZATION).filter(|_| options.forward_proxy_auth),
+    );
+
+    if let HeaderPolicy::Allowlist(allowed) = &options.header_policy {
+        debug!("Removing headers missing from the allowlist");
+
+        let dropped: Vec<HeaderName> = request
+            .headers()
+            .keys()
+            .filter(|name| !allowed.contains(*name) && !is_framing_header(name))
+            .cloned()
+            .collect();
+
+        for name in &dropped {
+            request.headers_mut().remove(name);
+        }
+        stripped.extend(dropped);
+    }
+
+    if let Some(trace) = trace {
+        trace.forward_uri = Some(uri.to_string());
+        stripped.extend(removed_hop_headers.names().cloned());
+        trace.stripped_request_headers = stripped;
+    }
+
+    *request.uri_mut() = uri;
+
+    if let Some(version) = options.force_version {
+        debug!("Forcing version {:?}", version);
+
+        *request.version_mut() = version;
+    }
+
+    if options.normalize_encoding {
+        debug!("Removing accept-encoding header");
+
+        request.headers_mut().remove(ACCEPT_ENCODING);
+    }
+
+    if contains_te_trailers_value {
+        debug!("Setting up trailer headers");
+
+        request
+            .headers_mut()
+            .insert(&*TE_HEADER, HeaderValue::from_static("trailers"));
+    }
+
+    if let Some(value) = upgrade_type {
+        debug!("Repopulate upgrade headers");
+
+        request
+            .headers_mut()
+            .insert(&*UPGRADE_HEADER, value.parse().unwrap());
+        request
+            .headers_mut()
+            .insert(&*CONNECTION_HEADER, HeaderValue::from_static("UPGRADE"));
+    }
+
+    if let Some(names) = &options.stripped_request_cookies {
+        strip_cookies(request.headers_mut(), names)?;
+    }
+
+    match &options.user_agent {
+        Some(Some(user_agent)) => {
+            debug!("Setting user-agent header");
+
+            request
+                .headers_mut()
+                .insert(USER_AGENT, user_agent.parse()?);
+        }
+        Some(None) => {
+            debug!("Removing user-agent header");
+
+            request.headers_mut().remove(USER_AGENT);
+        }
+        None => {}
+    }
+
+    if let Some(authorization) = &options.upstream_authorization {
+        debug!("Replacing authorization header with upstream credentials");
+
+        request
+            .headers_mut()
+            .insert(AUTHORIZATION, authorization.clone());
+    }
+
+    apply_forwarded_headers(request.headers_mut(), client_ip, real_ip, origin, options)?;
+
+    // signing comes last, so the signature covers the final request
+    #[cfg(feature = "signing")]
+    if let Some(signer) = &options.request_signer {
+        signer.sign(&mut request);
+    }
+
+    if options.mark_proxied {
+        request.extensions_mut().insert(ProxiedMarker);
+    }
+
+    debug!("Created proxied request");
+
+    Ok(request)
+}
+
+pub async fn call<T: hyper::client::connect::Connect + Clone + Send + Sync + 'static>(
+    client_ip: IpAddr,
+    forward_uri: &str,
+    request: Request<Body>,
+    client: &Client<T>,
+) -> Result<Response<Body>, ProxyError> {
+    call_with_options(
+        client_ip,
+        forward_uri,
+        request,
+        client,
+        &ProxyOptions::default(),
+        CallState::default(),
+    )
+    .await
+}
+
+/// Proxies a single request through a throwaway `Client<HttpConnector>`, requires the `http`
+/// feature.
+///
+/// Meant for CLI tools and scripts: connections are not pooled, every call opens a new one. Use a
+/// [`ReverseProxy`] to proxy more than a few requests.
+#[cfg(feature = "http")]
+pub async fn oneshot(
+    client_ip: IpAddr,
+    forward_uri: &str,
+    request: Request<Body>,
+) -> Result<Response<Body>, ProxyError> {
+    let client = Client::builder().pool_max_idle_per_host(0).build_http();
+
+    call(client_ip, forward_uri, request, &client).await
+}
+
+/// Evaluates the gate and the async request filter, returns the result of the call when one of
+/// them rejected the request.
+async fn admit_request(
+    request: &mut Request<Body>,
+    options: &ProxyOptions,
+) -> Option<Result<Response<Body>, ProxyError>> {
+    if let Some(gate) = &options.gate {
+        if let Err(response) = gate(request) {
+            debug!("Request rejected by gate");
+
+            return Some(Ok(response));
+        }
+    }
+
+    if let Some(filter) = &options.async_request_filter {
+        if let Err(err) = filter(request).await {
+            debug!("Request rejected by async filter: {}", err);
+
+            return Some(Err(err));
+        }
+    }
+
+    None
+}
+
+/// Forwards an admitted request, guarded by the circuit breaker and upstream limits.
+async fn call_upstream<T: hyper::client::connect::Connect + Clone + Send + Sync + 'static>(
+    client_ip: IpAddr,
+    forward_uri: &str,
+    request: Request<Body>,
+    client: &Client<T>,
+    options: &ProxyOptions,
+    state: CallState<'_>,
+) -> Result<Response<Body>, ProxyError> {
+    let tunnel_target = tunnel_target(&request, options);
+    let upstream = tunnel_target.as_deref().unwrap_or(forward_uri);
+
+    if let Some(breaker) = &options.circuit_breaker {
+        breaker.check(upstream)?;
+    }
+
+    let _permit = match &options.upstream_limits {
+        Some(limits) => Some(limits.acquire(upstream).await?),
+        None => None,
+    };
+
+    let result = proxy_request(client_ip, forward_uri, request, client, options, state).await;
+
+    if let Some(breaker) = &options.circuit_breaker {
+        breaker.record(upstream, &result);
+    }
+
+    result
+}
+
+async fn call_with_options<T: hyper::client::connect::Connect + Clone + Send + Sync + 'static>(
+    client_ip: IpAddr,
+    forward_uri: &str,
+    mut request: Request<Body>,
+    client: &Client<T>,
+    options: &ProxyOptions,
+    state: CallState<'_>,
+) -> Result<Response<Body>, ProxyError> {
+    let CallState {
+        mut trace,
+        timing,
+        cancel,
+        host,
+        client_cert,
+        admitted,
+    } = state;
+
+    // rejected requests neither count for the circuit breaker nor take an upstream permit
+    let rejected = match admitted {
+        true => None,
+        false => admit_request(&mut request, options).await,
+    };
+    let result = match rejected {
+        Some(rejected) => rejected,
+        None => {
+            call_upstream(
+                client_ip,
+                forward_uri,
+                request,
+                client,
+                options,
+                CallState {
+                    trace: trace.as_deref_mut(),
+                    timing,
+                    cancel,
+                    host,
+                    client_cert,
+                    admitted,
+                },
+            )
+            .await
+        }
+    };
+
+    let result = match (result, options.unavailable_retry_after) {
+        (Err(ProxyError::ConnectError { uri, .. }), Some(retry_after)) => {
+            debug!("Backend {} unreachable, responding with 503", uri);
+
+            Ok(Response::builder()
+                .status(StatusCode::SERVICE_UNAVAILABLE)
+                .header(RETRY_AFTER, retry_after.as_secs())
+                .body(Body::empty())?)
+        }
+        (result, _) => result,
+    };
+
+    if let (Some(trace), Ok(response)) = (trace, &result) {
+        trace.status = Some(response.status());
+    }
+
+    #[cfg(feature = "metrics")]
+    if let Some(metrics) = &options.metrics {
+        metrics::record_result(metrics.as_ref(), &result);
+    }
+
+    result
+}
+
+/// Picks the index of an entry of `upstreams` with a probability proportional to its weight.
+fn pick_weighted(upstreams: &[(&str, u32)]) -> usize {
+    let total: u64 = upstreams.iter().map(|(_, weight)| u64::from(*weight)).sum();
+    let mut point = rand::thread_rng().gen_range(0..total);
+
+    upstreams
+        .iter()
+        .position(|(_, weight)| match point.checked_sub(u64::from(*weight)) {
+            Some(rest) => {
+                point = rest;
+                false
+            }
+            None => true,
+        })
+        .expect("point is below the total weight")
+}
+
+async fn send_request<T: hyper::client::connect::Connect + Clone + Send + Sync + 'static>(
+    client: &Client<T>,
+    request: Request<Body>,
+    cancel: Option<&CancellationToken>,
+) -> Result<Response<Body>, ProxyError> {
+    let uri = request.uri().clone();
+    let response = client.request(request);
+    let response = match cancel {
+        Some(token) => token.run_until_cancelled(response).await.ok_or_else(|| {
+            debug!("Call cancelled while waiting for the backend");
+
+            ProxyError::Cancelled
+        })?,
+        None => response.await,
+    };
+
+    response.map_err(|err| map_client_error(err, &uri))
+}
+
+/// Removes `.` and `..` segments from `path` as in RFC 3986 section 5.2.4.
+fn remove_dot_segments(path: &str) -> String {
+    let mut output: Vec<&str> = Vec::new();
+    let segments: Vec<&str> = path.split('/').collect();
+
+    for (index, segment) in segments.iter().enumerate() {
+        let last = index + 1 == segments.len();
+
+        match *segment {
+            "." | ".." => {
+                if *segment == ".." && output.len() > 1 {
+                    output.pop();
+                }
+
+                // a trailing dot segment still names a directory
+                if last {
+                    output.push("");
+                }
+            }
+            segment => output.push(segment),
+        }
+    }
+
+    let path = output.join("/");
+
+    if path.starts_with('/') {
+        path
+    } else {
+        format!("/{}", path.trim_start_matches('/'))
+    }
+}
+
+/// Resolves the `reference` of a `Location` header against `base` as in RFC 3986 section 5.2.2.
+/// The fragment is dropped, it is never sent to the backend.
+fn resolve_reference(base: &Uri, reference: &str) -> Option<Uri> {
+    let reference = reference.split('#').next().unwrap_or("");
+
+    if let Ok(target) = reference.parse::<Uri>() {
+        if target.scheme().is_some() {
+            return Some(target);
+        }
+    }
+
+    if reference.starts_with("//") {
+        return format!("{}:{}", base.scheme_str()?, reference).parse().ok();
+    }
+
+    let (path, query) = match reference.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (reference, None),
+    };
+    let path = if path.is_empty() {
+        base.path().to_string()
+    } else if path.starts_with('/') {
+        remove_dot_segments(path)
+    } else {
+        // relative to the directory of the current path
+        let base_path = base.path();
+        remove_dot_segments(&format!(
+            "{}{}",
+            &base_path[..base_path.rfind('/').map_or(0, |index| index + 1)],
+            path
+        ))
+    };
+    let query = match query {
+        Some(query) => Some(query),
+        None if reference.is_empty() => base.query(),
+        None => None,
+    };
+    let path_and_query = match query {
+        Some(query) => format!("{}?{}", path, query),
+        None => path,
+    };
+
+    let mut parts = base.clone().into_parts();
+    parts.path_and_query = Some(path_and_query.parse().ok()?);
+    Uri::from_parts(parts).ok()
+}
+
+/// The target of a redirect `response` to a request for `uri`, when it stays on the same scheme
+/// and authority.
+fn internal_redirect(uri: &Uri, response: &Response<Body>) -> Option<Uri> {
+    if !matches!(response.status().as_u16(), 301 | 302 | 303 | 307 | 308) {
+        return None;
+    }
+
+    let location = response.headers().get(LOCATION)?.to_str().ok()?;
+    let target = resolve_reference(uri, location)?;
+
+    if target.scheme() == uri.scheme() && target.authority() == uri.authority() {
+        Some(target)
+    } else {
+        None
+    }
+}
+
+/// Fails with an `UpgradeError` carrying `message`, or answers it as `502 Bad Gateway` with
+/// `upgrade_mismatch_response`.
+fn upgrade_mismatch(options: &ProxyOptions, message: String) -> Result<Response<Body>, ProxyError> {
+    if !options.upgrade_mismatch_response {
+        return Err(ProxyError::UpgradeError(message));
+    }
+
+    debug!("Answering upgrade mismatch with 502: {}", message);
+
+    Ok(Response::builder()
+        .status(StatusCode::BAD_GATEWAY)
+        .body(Body::from(message))?)
+}
+
+/// Spawns the task copying between the upgraded `backend` connection and the client's connection
+/// once `request_upgraded` resolves.
+fn spawn_upgraded_session<B>(
+    backend: B,
+    request_upgraded: OnUpgrade,
+    options: &ProxyOptions,
+    cancel: Option<&CancellationToken>,
+) where
+    B: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let on_upgrade_complete = options.on_upgrade_complete.clone();
+    let idle_timeout = options.upgrade_idle_timeout;
+    let buffer_size = options
+        .upgrade_buffer_size
+        .unwrap_or(DEFAULT_UPGRADE_BUFFER_SIZE);
+    let cancel = cancel.cloned();
+    let active = options.active_upgrades.start();
+
+    tokio::spawn(async move {
+        let _active = active;
+        let session = async {
+            let result = match request_upgraded.await {
+                Ok(request_upgraded) => {
+                    copy_upgraded(backend, request_upgraded, idle_timeout, buffer_size).await
+                }
+                Err(err) => Err(std::io::Error::other(err)),
+            };
+
+            match &result {
+                Ok((backend_to_client, client_to_backend)) => debug!(
+                    "Upgraded connection closed after {} bytes to the client and {} bytes to the backend",
+                    backend_to_client, client_to_backend
+                ),
+                Err(err) if err.kind() == std::io::ErrorKind::TimedOut => {
+                    debug!("Closing idle upgraded connection")
+                }
+                Err(err) => {
+                    error!("Copying between upgraded connections failed: {}", err)
+                }
+            }
+
+            result
+        };
+
+        let result = match cancel {
+            Some(token) => token.run_until_cancelled(session).await.unwrap_or_else(|| {
+                debug!("Upgraded connection cancelled");
+
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::Interrupted,
+                    "upgraded connection was cancelled",
+                ))
+            }),
+            None => session.await,
+        };
+
+        if let Some(callback) = on_upgrade_complete {
+            callback(result);
+        }
+    });
+}
+
+/// The authority a `CONNECT` request is tunneled to, `None` for other requests or without
+/// `tunnel_connect`. It stands in for the forward URI of circuit breaker and upstream limits.
+fn tunnel_target<B>(request: &Request<B>, options: &ProxyOptions) -> Option<String> {
+    if options.tunnel_connect && request.method() == Method::CONNECT {
+        request
+            .uri()
+            .authority()
+            .map(|authority| authority.to_string())
+    } else {
+        None
+    }
+}
+
+/// Answers a `CONNECT` request by opening a TCP connection to its authority, then tunneling the
+/// client's connection to it once the `200` response was sent.
+async fn tunnel_connect(
+    mut request: Request<Body>,
+    options: &ProxyOptions,
+    cancel: Option<&CancellationToken>,
+) -> Result<Response<Body>, ProxyError> {
+    let authority = request
+        .uri()
+        .authority()
+        .ok_or_else(|| ProxyError::UpgradeError("CONNECT request without authority".to_string()))?
+        .to_string();
+    let connect_error = |err: std::io::Error| ProxyError::ConnectError {
+        uri: authority.clone(),
+        source: Box::new(err),
+    };
+
+    // resolving once and dialing the checked addresses leaves no room for DNS rebinding
+    let addresses = match &options.block_private_addresses {
+        Some(resolved) => {
+            let addresses = resolved.resolve(&authority).await.map_err(connect_error)?;
+            check_addresses(&authority, addresses.iter().map(|addr| addr.ip()))?;
+
+            addresses
+        }
+        None => tokio::net::lookup_host(authority.as_str())
+            .await
+            .map_err(connect_error)?
+            .collect(),
+    };
+
+    debug!("Tunneling CONNECT request to {}", authority);
+
+    let backend = tokio::net::TcpStream::connect(&addresses[..])
+        .await
+        .map_err(connect_error)?;
+    let request_upgraded = request
+        .extensions_mut()
+        .remove::<OnUpgrade>()
+        .ok_or_else(|| {
+            ProxyError::UpgradeError("request does not have an upgrade extension".to_string())
+        })?;
+
+    spawn_upgraded_session(backend, request_upgraded, options, cancel);
+
+    Ok(Response::new(Body::empty()))
+}
+
+async fn proxy_request<T: hyper::client::connect::Connect + Clone + Send + Sync + 'static>(
+    client_ip: IpAddr,
+    forward_uri: &str,
+    mut request: Request<Body>,
+    client: &Client<T>,
+    options: &ProxyOptions,
+    state: CallState<'_>,
+) -> Result<Response<Body>, ProxyError> {
+    let CallState {
+        mut trace,
+        mut timing,
+        cancel,
+        host,
+        client_cert,
+        ..
+    } = state;
+    let started = Instant::now();
+
+    info!(
+        "Received proxy call from {} to {}, client: {}",
+        request.uri().to_string(),
+        forward_uri,
+        client_ip
+    );
+
+    if options.tunnel_connect && request.method() == Method::CONNECT {
+        if let Some(trace) = trace {
+            trace.upgraded = true;
+        }
+
+        if let Some(timing) = timing {
+            timing.upgraded = true;
+        }
+
+        return tunnel_connect(request, options, cancel).await;
+    }
+
+    let request_upgrade_type = get_upgrade_type(request.headers());
+    let request_websocket_protocols = get_websocket_protocols(request.headers());
+    let request_upgraded = request.extensions_mut().remove::<OnUpgrade>();
+
+    if let Some(methods) = &options.stripped_body_methods {
+        if methods.contains(request.method()) {
+            debug!("Dropping request body of {} request", request.method());
+
+            request.headers_mut().remove(CONTENT_LENGTH);
+            request.headers_mut().remove(TRANSFER_ENCODING);
+            *request.body_mut() = Body::empty();
+        }
+    }
+
+    let body_abort = BodyAbort::default();
+
+    if let Some(limit) = options.max_request_body {
+        let content_length = request
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok());
+
+        if content_length.map(|length| length > limit).unwrap_or(false) {
+            debug!("Request content-length exceeds limit of {} bytes", limit);
+
+            return Err(ProxyError::RequestBodyTooLarge);
+        }
+
+        request = request.map(|body| limit_body(body, limit, body_abort.clone()));
+    }
+
+    if let Some(timeout) = options.request_body_read_timeout {
+        request = request.map(|body| timeout_body(body, timeout, body_abort.clone()));
+    }
+
+    let mut proxied_request = create_proxied_request(
+        client_ip,
+        forward_uri,
+        request,
+        request_upgrade_type.as_ref(),
+        options,
+        trace.as_deref_mut(),
+    )?;
+
+    if let Some(resolved) = &options.block_private_addresses {
+        let checked = check_forward_address(proxied_request.uri(), resolved).await?;
+
+        // dialing the checked address leaves no room for DNS rebinding, `https` keeps the host
+        // name for certificate verification
+        if let Some(addr) =
+            checked.filter(|_| proxied_request.uri().scheme() != Some(&Scheme::HTTPS))
+        {
+            let mut parts = proxied_request.uri().clone().into_parts();
+            let authority = parts.authority.replace(addr.to_string().parse()?);
+            *proxied_request.uri_mut() =
+                Uri::from_parts(parts).expect("only the authority was changed");
+
+            if let Some(authority) = authority {
+                debug!("Connecting to checked address {} of {}", addr, authority);
+
+                proxied_request
+                    .headers_mut()
+                    .insert(HOST, authority.as_str().parse()?);
+            }
+        }
+    }
+
+    if let Some(host) = host {
+        debug!("Setting host header to {}", host);
+
+        proxied_request.headers_mut().insert(HOST, host.parse()?);
+    }
+
+    if let Some(cert) = client_cert {
+        set_client_cert_headers(proxied_request.headers_mut(), cert, options);
+    }
+
+    if let Some(transform) = &options.body_transform {
+        let (mut parts, mut body) = proxied_request.into_parts();
+        apply_body_transform(&mut parts.headers, &mut body, |headers, body| {
+            transform.transform_request(headers, body)
+        });
+        proxied_request = Request::from_parts(parts, body);
+    }
+
+    if let Some(timing) = timing.as_deref_mut() {
+        timing.request_built = started.elapsed();
+    }
+
+    let redirect_template = match options.follow_internal_redirects {
+        Some(max_hops) if matches!(*proxied_request.method(), Method::GET | Method::HEAD) => {
+            Some((
+                max_hops,
+                proxied_request.method().clone(),
+                proxied_request.version(),
+                proxied_request.headers().clone(),
+            ))
+        }
+        _ => None,
+    };
+    let mut proxied_uri = proxied_request.uri().clone();
+    let mut response = send_request(client, proxied_request, cancel)
+        .await
+        .map_err(|err| body_abort.take().unwrap_or(err))?;
+
+    if let Some((max_hops, method, version, headers)) = redirect_template {
+        let mut hops = 0;
+
+        while let Some(location) = internal_redirect(&proxied_uri, &response) {
+            if hops == max_hops {
+                debug!("Stopped following redirects after {} hops", hops);
+                break;
+            }
+            hops += 1;
+            debug!("Following internal redirect to {}", location);
+
+            let mut request = Request::new(Body::empty());
+            *request.method_mut() = method.clone();
+            *request.uri_mut() = location.clone();
+            *request.version_mut() = version;
+            *request.headers_mut() = headers.clone();
+
+            // the signature covers the path, so every hop is signed again
+            #[cfg(feature = "signing")]
+            if let Some(signer) = &options.request_signer {
+                signer.sign(&mut request);
+            }
+
+            response = send_request(client, request, cancel).await?;
+            proxied_uri = location;
+        }
+    }
+
+    if let Some(last_status) = &options.last_upstream_status {
+        last_status.store(response.status().as_u16(), Ordering::Relaxed);
+    }
+
+    if let Some(limit) = options.max_response_header_bytes {
+        let size: usize = response
+            .headers()
+            .iter()
+            .map(|(name, value)| name.as_str().len() + value.len())
+            .sum();
+
+        if size > limit {
+            debug!(
+                "Response headers of {} bytes exceed limit of {}",
+                size, limit
+            );
+
+            return Err(ProxyError::ResponseHeadersTooLarge);
+        }
+    }
+
+    if let Some(timing) = timing.as_deref_mut() {
+        timing.response_headers = started.elapsed();
+    }
+
+    #[cfg(feature = "http")]
+    if let Some(trace) = trace.as_deref_mut() {
+        trace.connection = response.extensions().get::<HttpInfo>().cloned();
+    }
+
+    if is_upgrade_response(&response) {
+        let response_upgrade_type = get_upgrade_type(response.headers());
+
+        // both types are `None` then, which would pass the comparison below
+        if request_upgrade_type.is_none() {
+            debug!("Backend switched protocols without an upgrade request");
+
+            return upgrade_mismatch(
+                options,
+                format!(
+                    "backend switched protocols to {:?} but the client did not request an upgrade",
+                    response_upgrade_type
+                ),
+            );
+        }
+
+        if request_upgrade_type == response_upgrade_type {
+            check_websocket_protocol(&request_websocket_protocols, response.headers())?;
+
+            if let Some(request_upgraded) = request_upgraded {
+                let response_upgraded = response
+                    .extensions_mut()
+                    .remove::<OnUpgrade>()
+                    .expect("response does not have an upgrade extension")
+                    .await?;
+
+                debug!("Responding to a connection upgrade response");
+
+                if let Some(pseudonym) = &options.via_pseudonym {
+                    append_via(response.headers_mut(), pseudonym)?;
+                }
+
+                if let Some((server, overwrite)) = &options.server_header {
+                    set_server_header(response.headers_mut(), server, *overwrite)?;
+                }
+
+                if let Some(trace) = trace {
+                    trace.upgraded = true;
+                }
+
+                if let Some(timing) = timing {
+                    timing.upgraded = true;
+                }
+
+                spawn_upgraded_session(response_upgraded, request_upgraded, options, cancel);
+
+                if options.mark_proxied {
+                    response.extensions_mut().insert(ProxiedMarker);
+                }
+
+                Ok(response)
+            } else {
+                Err(ProxyError::UpgradeError(
+                    "request does not have an upgrade extension".to_string(),
+                ))
+            }
+        } else {
+            upgrade_mismatch(
+                options,
+                format!(
+                    "backend tried to switch to protocol {:?} when {:?} was requested",
+                    response_upgrade_type, request_upgrade_type
+                ),
+            )
+        }
+    } else {
+        if let Some(status_override) = &options.status_override {
+            if let Some(replacement) = status_override(response.status()) {
+                debug!(
+                    "Replacing backend response with status {}",
+                    response.status()
+                );
+
+                return Ok(replacement);
+            }
+        }
+
+        // HTTP/2 peers may send trailers without announcing them
+        let may_have_trailers = response.version() == Version::HTTP_2
+            || response.headers().contains_key(&*TRAILER_HEADER);
+        let (mut parts, mut body) = create_proxied_response(response, options, trace).into_parts();
+
+        // before filtering the trailers, which hides whether the body is empty
+        if let Some(map) = &options.response_body_map {
+            apply_body_transform(&mut parts.headers, &mut body, |_, body| {
+                debug!("Mapping response body");
+
+                map_body(body, map.clone())
+            });
+        }
+
+        if let Some(transform) = &options.blocking_response_transform {
+            apply_body_transform(&mut parts.headers, &mut body, |_, body| {
+                debug!("Transforming response body on the blocking pool");
+
+                transform_body_blocking(body, transform.clone())
+            });
+        }
+
+        if let Some(transform) = &options.body_transform {
+            apply_body_transform(&mut parts.headers, &mut body, |headers, body| {
+                transform.transform_response(headers, body)
+            });
+        }
+
+        let mut proxied_response = Response::from_parts(parts, body);
+
+        if may_have_trailers {
+            proxied_response = proxied_response.map(filter_trailers);
+        }
+
+        if let Some(pseudonym) = &options.via_pseudonym {
+            append_via(proxied_response.headers_mut(), pseudonym)?;
+        }
+
+        if let Some((server, overwrite)) = &options.server_header {
+            set_server_header(proxied_response.headers_mut(), server, *overwrite)?;
+        }
+
+        if let Some(token) = cancel {
+            proxied_response = proxied_response.map(|body| cancellable_body(body, token.clone()));
+        }
+
+        if options.mark_proxied {
+            proxied_response.extensions_mut().insert(ProxiedMarker);
+        }
+
+        debug!("Responding to call with response");
+        Ok(proxied_response)
+    }
+}
+
+/// A reverse proxy forwarding requests through a shared `Client`.
+///
+/// Cloning is cheap: clones share the client's connection pool and the proxy's settings, so a
+/// clone can be moved into every `service_fn` instead of wrapping the proxy in an `Arc`.
+///
+/// Response bodies are streamed to the client as the backend sends them, nothing is buffered, so
+/// long-lived streams like Server-Sent Events (`text/event-stream`) are forwarded as they happen.
+///
+/// Response trailers are forwarded, minus hop-by-hop fields, when both the backend and the
+/// client connection use HTTP/2. hyper does not support trailers over HTTP/1.
+///
+/// HTTP/2 backends cannot push responses nobody asked for: hyper's client disables server push
+/// in its connection settings, so push attempts fail on the backend and never reach the proxy.
+///
+/// Of the `TE` request header only the `trailers` token is forwarded, other transfer codings
+/// like `gzip` are dropped since they only apply to the client's connection.
+///
+/// `Expect: 100-continue` is forwarded to the backend like any end-to-end header. Interim
+/// responses are not relayed as such: hyper's server sends the client its own `100 Continue` once
+/// the proxy starts reading the request body, and hyper's client skips the backend's `100
+/// Continue` while waiting for the final response.
+#[derive(Clone)]
+pub struct ReverseProxy<T: hyper::client::connect::Connect + Clone + Send + Sync + 'static> {
+    client: Client<T>,
+    connector: Option<T>,
+    options: ProxyOptions,
+}
+
+impl<T: hyper::client::connect::Connect + Clone + Send + Sync + 'static> ReverseProxy<T> {
+    pub fn new(client: Client<T>) -> Self {
+        Self {
+            client,
+            connector: None,
+            options: ProxyOptions::default(),
+        }
+    }
+
+    /// Creates a proxy using a default `Client` built from `connector`.
+    ///
+    /// Unlike `new`, the proxy keeps a handle to the connector, which enables `verify_upstream`.
+    ///
+    /// TLS is left to the connector, which takes the SNI hostname from the host of the forward
+    /// URI. To reach a backend by IP while presenting another SNI hostname, forward to that
+    /// hostname and build the connector on a [`PinnedResolver`] resolving it to the IP.
+    ///
+    /// The proxy does not interpret the scheme of forward URIs, so a connector can claim its own,
+    /// e.g. `h3://` for HTTP/3 backends: it receives the full URI in `Service<Uri>::call`, while
+    /// `HttpConnector` rejects schemes other than `http`. hyper 0.14 itself still speaks HTTP/1 or
+    /// HTTP/2 over the returned connection, so such a connector has to bridge to an HTTP/3 client
+    /// like the `h3` crate. Allow the scheme explicitly when using `allowed_schemes`.
+    pub fn with_connector(connector: T) -> Self {
+        Self {
+            client: Client::builder().build(connector.clone()),
+            connector: Some(connector),
+            options: ProxyOptions::default(),
+        }
+    }
+
+    /// Rejects calls whose forward URI resolves to a loopback, link-local or private address
+    /// with `ProxyError::ForbiddenAddress`, for forward URIs derived from user input.
+    ///
+    /// Host names are resolved once per call, reusing the addresses for 30 seconds, and the call
+    /// connects to the first checked address with the `Host` header set to the forward URI's, so a
+    /// DNS server changing its answer in between is not followed. For `https` forward URIs the
+    /// connector still resolves the name, which certificate verification needs, and a rebound
+    /// name then fails the TLS handshake unless the address serves a certificate for it.
+    pub fn block_private_addresses(mut self, enabled: bool) -> Self {
+        self.options.block_private_addresses = if enabled {
+            Some(Arc::new(ResolvedAddresses::default()))
+        } else {
+            None
+        };
+        self
+    }
+
+    /// Evaluates `gate` before anything else in a call.
+    ///
+    /// When it returns `Err(response)`, that response is returned right away and the backend is
+    /// never contacted, e.g. to reject methods or paths with a custom status.
+    pub fn with_gate<F>(mut self, gate: F) -> Self
+    where
+        F: Fn(&Request<Body>) -> Result<(), Response<Body>> + Send + Sync + 'static,
+    {
+        self.options.gate = Some(Arc::new(gate));
+        self
+    }
+
+    /// Awaits `filter` after the gate and before the request is forwarded, for changes that need
+    /// async work, e.g. fetching a token from a cache.
+    ///
+    /// The filter sees the client's request, so hop-by-hop headers it adds are still removed.
+    /// Returning `Err` fails the call with that error.
+    pub fn with_async_request_filter<F>(mut self, filter: F) -> Self
+    where
+        F: for<'a> Fn(&'a mut Request<Body>) -> BoxFuture<'a, Result<(), ProxyError>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.options.async_request_filter = Some(Arc::new(filter));
+        self
+    }
+
+    /// Strips the `Accept-Encoding` header from proxied requests, so the backend answers with
+    /// the identity encoding instead of a `Content-Encoding` the client may not have asked for.
+    pub fn normalize_encoding(mut self, normalize: bool) -> Self {
+        self.options.normalize_encoding = normalize;
+        self
+    }
+
+    /// Collapses `//` and resolves `.` and `..` segments of the request path before forwarding.
+    ///
+    /// Paths escaping above the forward URL's base path, like `/../secret`, fail with
+    /// `ProxyError::InvalidPath` instead of being forwarded.
+    pub fn normalize_path(mut self, normalize: bool) -> Self {
+        self.options.normalize_path = normalize;
+        self
+    }
+
+    /// Forwards absolute-form requests (`GET http://example.com/path`) to the scheme and authority
+    /// of their target instead of the `forward_uri` of the call, for forward proxy setups.
+    ///
+    /// This lets clients pick any backend, so restrict the allowed targets, e.g. with a gate.
+    /// Origin-form requests still use `forward_uri`. Only HTTP/1 requests are routed this way:
+    /// hyper's server gives HTTP/2 requests an absolute URI built from `:scheme` and `:authority`,
+    /// which would route every HTTP/2 request back to the proxy itself.
+    pub fn forward_absolute_form(mut self, enabled: bool) -> Self {
+        self.options.forward_absolute_form = enabled;
+        self
+    }
+
+    /// Only forwards to URIs with one of `schemes`, e.g. `&["http", "https"]`, as a defense
+    /// against SSRF when the forward URI is derived from untrusted input.
+    ///
+    /// Other schemes fail with `ProxyError::DisallowedScheme` before the backend is contacted.
+    pub fn allowed_schemes(mut self, schemes: &[&str]) -> Self {
+        self.options.allowed_schemes =
+            Some(schemes.iter().map(|scheme| scheme.to_string()).collect());
+        self
+    }
+
+    /// Sends every proxied request with `version` instead of the version the client used.
+    ///
+    /// The client's version is negotiated with the proxy (via ALPN for TLS) independently of the
+    /// backend connection, e.g. an HTTP/2 client may talk to an HTTP/1 backend. The forced version
+    /// must be one the backend connection speaks: hyper refuses to send `HTTP/2` requests over
+    /// HTTP/1 connections, those need a client negotiating h2 or using `http2_only`.
+    pub fn force_version(mut self, version: Version) -> Self {
+        self.options.force_version = Some(version);
+        self
+    }
+
+    /// Replaces the scheme of the forward URI with the [`InboundScheme`] found in the request
+    /// extensions, so the backend sees the scheme the client used.
+    ///
+    /// The scheme also decides how the client connects, so its connector must support both, e.g.
+    /// an `HttpConnector` with `enforce_http(false)` to reach a plain HTTP backend under an
+    /// `https` URI. Requests without an `InboundScheme` keep the forward URI's scheme.
+    pub fn forward_inbound_scheme(mut self, enabled: bool) -> Self {
+        self.options.forward_inbound_scheme = enabled;
+        self
+    }
+
+    /// Answers `503 Service Unavailable` with `Retry-After: <seconds>` instead of failing with
+    /// `ProxyError::ConnectError` when the backend is unreachable, giving clients a backoff signal.
+    pub fn unavailable_retry_after(mut self, retry_after: Duration) -> Self {
+        self.options.unavailable_retry_after = Some(retry_after);
+        self
+    }
+
+    /// Sends `Authorization: Basic` with `user` and `password` to the backend, replacing any
+    /// `Authorization` header of the client, which therefore never reaches the backend.
+    ///
+    /// The header is marked sensitive, so it is left out of hpack compression tables.
+    pub fn upstream_basic_auth(mut self, user: &str, password: &str) -> Self {
+        let credentials = base64_encode(format!("{}:{}", user, password).as_bytes());
+        let mut value = HeaderValue::from_str(&format!("Basic {}", credentials))
+            .expect("base64 is a valid header value");
+        value.set_sensitive(true);
+
+        self.options.upstream_authorization = Some(value);
+        self
+    }
+
+    /// Limits the request body to `max_bytes`.
+    ///
+    /// The limit is enforced while the body is streamed to the backend, so nothing is buffered.
+    /// Once exceeded, the call fails with `ProxyError::RequestBodyTooLarge`.
+    pub fn max_request_body(mut self, max_bytes: u64) -> Self {
+        self.options.max_request_body = Some(max_bytes);
+        self
+    }
+
+    /// Fails the call with `ProxyError::ResponseHeadersTooLarge` when the backend's response
+    /// headers, summing the lengths of all names and values, exceed `max_bytes`.
+    ///
+    /// hyper's client caps the whole response head with its own, larger buffer limit already.
+    pub fn max_response_header_bytes(mut self, max_bytes: usize) -> Self {
+        self.options.max_response_header_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Fails the call with `ProxyError::RequestBodyTimeout` when the client sends no request body
+    /// data for `timeout`, so a client trickling its body cannot hold a backend connection.
+    ///
+    /// The timeout applies between chunks, a slow but steady upload is not cut off.
+    pub fn request_body_read_timeout(mut self, timeout: Duration) -> Self {
+        self.options.request_body_read_timeout = Some(timeout);
+        self
+    }
+
+    /// Replaces the request body with an empty one for the given `methods`, for backends that
+    /// mishandle a body on e.g. `GET` or `DELETE`.
+    pub fn strip_body_for(mut self, methods: &[Method]) -> Self {
+        self.options.stripped_body_methods = Some(methods.to_vec());
+        self
+    }
+
+    /// Stops adding the client IP to `X-Forwarded-For`, or the header set with
+    /// `forwarded_for_header`, and removes the header sent by the client, for networks where the
+    /// client IP must not reach the backend.
+    pub fn disable_forwarded_for(mut self, disabled: bool) -> Self {
+        self.options.disable_forwarded_for = disabled;
+        self
+    }
+
+    /// Limits the entries of the client's `X-Forwarded-For` header to `max`, so a client cannot
+    /// inflate the header passed on to the backend. The client IP appended by the proxy is not
+    /// counted.
+    pub fn max_forwarded_for_entries(mut self, max: usize, limit: ForwardedForLimit) -> Self {
+        self.options.max_forwarded_for_entries = Some((max, limit));
+        self
+    }
+
+    /// Sets the separator between `X-Forwarded-For` entries, by default `, `.
+    ///
+    /// With `ForwardedForSeparator::Comma` the client's entries are re-joined as well, whichever
+    /// separator the client used.
+    pub fn forwarded_for_separator(mut self, separator: ForwardedForSeparator) -> Self {
+        self.options.forwarded_for_separator = separator;
+        self
+    }
+
+    /// Sets the header the client IP is appended to, defaults to `X-Forwarded-For`.
+    pub fn forwarded_for_header(mut self, header: HeaderName) -> Self {
+        self.options.forwarded_for_header = Some(header);
+        self
+    }
+
+    /// Only keeps an incoming `X-Forwarded-For` when the request comes from one of the
+    /// `trusted_proxies`.
+    ///
+    /// For any other `client_ip` the client supplied value is dropped before the client IP is
+    /// added, so clients cannot spoof their address. Without this option every incoming value is
+    /// trusted.
+    pub fn trusted_proxies(mut self, trusted_proxies: Vec<IpNet>) -> Self {
+        self.options.trusted_proxies = Some(trusted_proxies);
+        self
+    }
+
+    /// Sets where the client address sent as `X-Real-IP` comes from, by default the `client_ip`
+    /// passed to the call.
+    ///
+    /// The forwarded for sources only look past `client_ip` when it is one of the
+    /// `trusted_proxies`, so clients cannot spoof their address. Without `trusted_proxies` no
+    /// peer is trusted and they always yield `client_ip`. The forwarded for header is not
+    /// affected: whether the incoming chain is kept and which address is appended is always
+    /// decided by `client_ip`, see `trusted_proxies`.
+    pub fn client_ip_source(mut self, source: ClientIpSource) -> Self {
+        self.options.client_ip_source = source;
+        self
+    }
+
+    /// Sets the forwarded for header to the originating client only, instead of appending to it.
+    ///
+    /// That is the right-most address of the incoming chain followed by `client_ip` that is not
+    /// one of the `trusted_proxies`, or the left-most one when all are trusted. Without
+    /// `trusted_proxies` no address is trusted, so it is always `client_ip`.
+    pub fn collapse_forwarded_for(mut self, enabled: bool) -> Self {
+        self.options.collapse_forwarded_for = enabled;
+        self
+    }
+
+    /// Sets which client request headers are forwarded, by default all but the hop-by-hop ones.
+    ///
+    /// With `HeaderPolicy::Allowlist` headers set by the proxy itself, like `X-Forwarded-For` or
+    /// `Via`, are still added after the client's headers were filtered.
+    pub fn header_policy(mut self, policy: HeaderPolicy) -> Self {
+        self.options.header_policy = policy;
+        self
+    }
+
+    /// Overwrites the `User-Agent` of proxied requests with `Some(user_agent)`, or removes it with
+    /// `None`. By default the client's `User-Agent` is forwarded.
+    pub fn set_user_agent(mut self, user_agent: Option<String>) -> Self {
+        self.options.user_agent = Some(user_agent);
+        self
+    }
+
+    /// Sets `X-Real-IP` to the client IP on proxied requests, replacing any value sent by the
+    /// client.
+    pub fn set_x_real_ip(mut self, enabled: bool) -> Self {
+        self.options.x_real_ip = enabled;
+        self
+    }
+
+    /// Sets `X-Forwarded-Proto` to the scheme the client used, replacing any value sent by the
+    /// client: the [`InboundScheme`] when present, else the scheme of an absolute request URI or
+    /// `http`.
+    pub fn set_x_forwarded_proto(mut self, enabled: bool) -> Self {
+        self.options.x_forwarded_proto = enabled;
+        self
+    }
+
+    /// Sets `X-Forwarded-Host` to the `Host` the client requested, replacing any value sent by
+    /// the client.
+    pub fn set_x_forwarded_host(mut self, enabled: bool) -> Self {
+        self.options.x_forwarded_host = enabled;
+        self
+    }
+
+    /// Rewrites the `Domain` attribute of `Set-Cookie` response headers from `from` to `to`, so
+    /// browsers accept cookies a backend sets for its internal domain.
+    ///
+    /// The domains are compared case insensitively, ignoring a leading dot. Cookies for other or
+    /// without domains are left untouched.
+    pub fn rewrite_cookie_domain(mut self, from: String, to: String) -> Self {
+        self.options.cookie_domain_rewrite = Some((from, to));
+        self
+    }
+
+    /// Removes the cookies called `names` from the `Cookie` header of proxied requests, e.g. to
+    /// keep session cookies of other applications from leaking to the backend.
+    ///
+    /// The header is dropped when no cookie remains.
+    pub fn strip_request_cookies(mut self, names: &[&str]) -> Self {
+        self.options.stripped_request_cookies =
+            Some(names.iter().map(|name| name.to_string()).collect());
+        self
+    }
+
+    /// Sets the headers `call_with_client_cert` sends the certificate subject and fingerprint in.
+    pub fn client_cert_headers(mut self, subject: HeaderName, fingerprint: HeaderName) -> Self {
+        self.options.client_cert_headers = Some((subject, fingerprint));
+        self
+    }
+
+    /// Removes the headers called `names` from backend responses, e.g. `x-powered-by` or
+    /// `server` revealing details about the backend. Names are matched case-insensitively.
+    ///
+    /// A `Server` header set with `response_server_header` is still added afterwards.
+    ///
+    /// # Panics
+    ///
+    /// Panics when one of `names` is not a valid header name.
+    pub fn strip_response_headers(mut self, names: &[&str]) -> Self {
+        self.options.stripped_response_headers = names
+            .iter()
+            .map(|name| HeaderName::from_bytes(name.as_bytes()).expect("valid header name"))
+            .collect();
+        self
+    }
+
+    /// Sets the `Server` header of responses to `server`, e.g. to tell which proxy instance
+    /// served a response.
+    ///
+    /// When the backend already sent a `Server` header, `overwrite` replaces it, otherwise
+    /// `server` is appended to the backend's product tokens.
+    pub fn response_server_header(mut self, server: String, overwrite: bool) -> Self {
+        self.options.server_header = Some((server, overwrite));
+        self
+    }
+
+    /// Appends `1.1 <pseudonym>` to the `Via` header of proxied requests and responses, as
+    /// proxies should per RFC 7230 5.7.1.
+    pub fn via_pseudonym(mut self, pseudonym: String) -> Self {
+        self.options.via_pseudonym = Some(pseudonym);
+        self
+    }
+
+    /// Inserts a [`ProxiedMarker`] into the extensions of proxied requests and responses, so
+    /// middleware in the same process can tell them from ones handled directly.
+    pub fn mark_proxied(mut self, enabled: bool) -> Self {
+        self.options.mark_proxied = enabled;
+        self
+    }
+
+    /// Forwards `Proxy-Authorization` to the backend and `Proxy-Authenticate` back to the client
+    /// instead of stripping them as hop headers, for forward proxy setups where an upstream
+    /// service handles the proxy authentication.
+    pub fn forward_proxy_authorization(mut self, enabled: bool) -> Self {
+        self.options.forward_proxy_auth = enabled;
+        self
+    }
+
+    /// Records the status of the latest backend response, read with `last_upstream_status`, e.g.
+    /// as a simple liveness signal. Clones of the proxy share the recorded status.
+    pub fn track_last_upstream_status(mut self, enabled: bool) -> Self {
+        self.options.last_upstream_status = if enabled {
+            Some(Arc::new(AtomicU16::new(0)))
+        } else {
+            None
+        };
+        self
+    }
+
+    /// The status of the latest backend response, `None` before the first response or without
+    /// `track_last_upstream_status`.
+    pub fn last_upstream_status(&self) -> Option<StatusCode> {
+        let status = self.options.last_upstream_status.as_ref()?;
+
+        StatusCode::from_u16(status.load(Ordering::Relaxed)).ok()
+    }
+
+    /// Fails calls right away with `ProxyError::CircuitOpen` for `cooldown` once a forward URI
+    /// failed to connect `failures` times in a row, to give a struggling backend a break.
+    ///
+    /// After the cooldown calls are attempted again, one more connection failure reopens the
+    /// circuit and a response closes it. Clones of the proxy share the circuits. Requests
+    /// rejected by the gate or the async request filter never reach the backend, so they are
+    /// answered even while the circuit is open and do not count either way.
+    pub fn circuit_breaker(mut self, failures: u32, cooldown: Duration) -> Self {
+        self.options.circuit_breaker = Some(Arc::new(CircuitBreaker::new(failures, cooldown)));
+        self
+    }
+
+    /// Allows at most `max` calls to the same upstream, the scheme and authority of the forward
+    /// URI, at a time. A call takes its slot once the gate and async request filter passed it,
+    /// until the backend's response headers arrived, streaming the body is not counted.
+    ///
+    /// Without a free slot, a call waits up to `acquire_timeout` for one, or fails right away
+    /// when it is `None`, with `ProxyError::UpstreamBusy`. Clones of the proxy share the slots.
+    ///
+    /// # Panics
+    ///
+    /// Panics when `max` is `0`, which would let no call through.
+    pub fn max_concurrent_per_upstream(
+        mut self,
+        max: usize,
+        acquire_timeout: Option<Duration>,
+    ) -> Self {
+        assert!(
+            max > 0,
+            "max_concurrent_per_upstream needs at least one slot"
+        );
+
+        self.options.upstream_limits = Some(Arc::new(UpstreamLimits::new(max, acquire_timeout)));
+        self
+    }
+
+    /// Sets the size of the buffer used per direction to copy between upgraded connections,
+    /// `8 KiB` by default. Larger buffers can raise the throughput of busy tunnels at the cost
+    /// of memory per session. Sizes below `1 KiB` are raised to it.
+    pub fn upgrade_buffer_size(mut self, size: usize) -> Self {
+        self.options.upgrade_buffer_size = Some(size.max(MIN_UPGRADE_BUFFER_SIZE));
+        self
+    }
+
+    /// Follows redirects of the backend to the same scheme and authority, up to `max_hops` in a
+    /// row, and returns the final response instead. Once `max_hops` is reached, the redirect is
+    /// returned as it is.
+    ///
+    /// Only `GET` and `HEAD` requests are followed, since they have no body that would need to be
+    /// sent again. Redirects to other hosts are returned to the client as usual.
+    pub fn follow_internal_redirects(mut self, max_hops: usize) -> Self {
+        self.options.follow_internal_redirects = Some(max_hops);
+        self
+    }
+
+    /// Answers a backend switching to another protocol than the client requested with a `502
+    /// Bad Gateway` describing the mismatch, instead of failing with `ProxyError::UpgradeError`.
+    ///
+    /// The backend's `101` response is dropped, the client's connection is not upgraded.
+    pub fn upgrade_mismatch_response(mut self, enabled: bool) -> Self {
+        self.options.upgrade_mismatch_response = enabled;
+        self
+    }
+
+    /// Answers `CONNECT` requests by tunneling the client's connection to the requested
+    /// authority, ignoring the forward URI, as a forward proxy does.
+    ///
+    /// Any reachable host can be tunneled to, so restrict the targets, e.g. with `with_gate`.
+    /// The gate, the async request filter and `block_private_addresses` apply to the authority,
+    /// as do the circuit breaker and upstream limits, keyed by it instead of the forward URI.
+    /// `allowed_schemes` does not apply, a tunnel has no scheme. An unreachable authority fails
+    /// with `ProxyError::ConnectError`.
+    pub fn tunnel_connect(mut self, enabled: bool) -> Self {
+        self.options.tunnel_connect = enabled;
+        self
+    }
+
+    /// Evaluates `on_status` with the status of every backend response. Returning `Some(response)`
+    /// replaces the backend response, e.g. to hide the stack trace of a `500`, `None` passes it
+    /// through.
+    ///
+    /// Upgrade responses are not passed to `on_status`.
+    pub fn on_status<F>(mut self, on_status: F) -> Self
+    where
+        F: Fn(StatusCode) -> Option<Response<Body>> + Send + Sync + 'static,
+    {
+        self.options.status_override = Some(Arc::new(on_status));
+        self
+    }
+
+    /// Applies `map` to every chunk of the backend response body while it is streamed, e.g. to
+    /// inject a script tag into HTML or redact tokens.
+    ///
+    /// Chunk boundaries are arbitrary, decided by the backend connection, so the map must give
+    /// the same result however the body is split: a token to redact may start in one chunk and
+    /// end in the next. The map runs on the runtime, use `with_blocking_response_transform` for
+    /// CPU heavy work. `Content-Length` is removed, as the map may change the body length.
+    /// Empty bodies, e.g. of HEAD responses, are left alone and trailers are kept.
+    pub fn with_response_body_map<F>(mut self, map: F) -> Self
+    where
+        F: Fn(Bytes) -> Bytes + Send + Sync + 'static,
+    {
+        self.options.response_body_map = Some(Arc::new(map));
+        self
+    }
+
+    /// Passes request bodies through `transform.transform_request` before they are sent to the
+    /// backend, and response bodies through `transform.transform_response` before they are
+    /// returned, see [`BodyTransform`].
+    ///
+    /// Bodies known to be empty, e.g. of GET requests or HEAD responses, are left alone, as are
+    /// upgraded connections.
+    pub fn with_body_transform(mut self, transform: Arc<dyn BodyTransform>) -> Self {
+        self.options.body_transform = Some(transform);
+        self
+    }
+
+    /// Applies `transform` to every chunk of the backend response body.
+    ///
+    /// The transform runs on tokio's blocking thread pool via `spawn_blocking`, so CPU bound work
+    /// (compression, cryptography, ...) does not stall the runtime. Chunk boundaries are decided
+    /// by the backend connection, so the transform must not rely on them. Like
+    /// `with_response_body_map`, empty bodies are left alone and trailers are kept.
+    pub fn with_blocking_response_transform<F>(mut self, transform: F) -> Self
+    where
+        F: Fn(Bytes) -> Bytes + Send + Sync + 'static,
+    {
+        self.options.blocking_response_transform = Some(Arc::new(transform));
+        self
+    }
+
+    /// Registers a callback invoked when the copy between an upgraded client and backend
+    /// connection (e.g. a WebSocket session) finishes.
+    ///
+    /// On success it receives the number of bytes sent from the backend to the client and from
+    /// the client to the backend. Either side closing or resetting its connection ends the session
+    /// successfully, other I/O errors are logged and passed to the callback.
+    pub fn on_upgrade_complete<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(std::io::Result<(u64, u64)>) + Send + Sync + 'static,
+    {
+        self.options.on_upgrade_complete = Some(Arc::new(callback));
+        self
+    }
+
+    /// The number of upgraded connections (e.g. WebSockets) still being copied in the
+    /// background. Clones of the proxy share the count.
+    pub fn active_upgrades(&self) -> usize {
+        self.options.active_upgrades.count.load(Ordering::SeqCst)
+    }
+
+    /// Resolves once no upgraded connection is being copied anymore, e.g. to let sessions finish
+    /// on shutdown after the server stopped accepting connections.
+    ///
+    /// Sessions started while waiting are waited for as well. Combine with `call_with_cancel` or
+    /// a timeout to bound the wait.
+    pub async fn drain(&self) {
+        self.options.active_upgrades.drain().await
+    }
+
+    /// Closes upgraded connections once no bytes flowed in either direction for `timeout`, so
+    /// silent WebSocket sessions do not hold resources forever.
+    ///
+    /// The `on_upgrade_complete` callback then receives a `TimedOut` error.
+    pub fn upgrade_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.options.upgrade_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Stores responses of `call_cached` in `cache`.
+    #[cfg(feature = "cache")]
+    pub fn with_response_cache(mut self, cache: Arc<dyn ResponseCache>) -> Self {
+        self.options.response_cache = Some(cache);
+        self
+    }
+
+    /// Reports requests, errors and upgrades of every call to `metrics`.
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics(mut self, metrics: Arc<dyn ProxyMetrics>) -> Self {
+        self.options.metrics = Some(metrics);
+        self
+    }
+
+    /// Rewrites the request path with `regex` before the forward URI is built, replacing the first
+    /// match with `replacement`, which may refer to capture groups like `$1`. The query is kept.
+    ///
+    /// For example `^/api/v1/(.*)` and `/$1` forward `/api/v1/users?page=2` as `/users?page=2`.
+    /// Paths not matching are forwarded unchanged. Compiling a `Regex` is expensive, the proxy
+    /// compiles it once here rather than per request.
+    #[cfg(feature = "regex")]
+    pub fn path_rewrite(mut self, regex: Regex, replacement: String) -> Self {
+        self.options.path_rewrite = Some((regex, replacement));
+        self
+    }
+
+    /// Signs every proxied request with `signer`, after all other changes to the request.
+    ///
+    /// See the [`HmacSigner`] documentation for the canonicalization rules.
+    #[cfg(feature = "signing")]
+    pub fn with_request_signer(mut self, signer: HmacSigner) -> Self {
+        self.options.request_signer = Some(signer);
+        self
+    }
+
+    pub async fn call(
+        &self,
+        client_ip: IpAddr,
+        forward_uri: &str,
+        request: Request<Body>,
+    ) -> Result<Response<Body>, ProxyError> {
+        call_with_options::<T>(
+            client_ip,
+            forward_uri,
+            request,
+            &self.client,
+            &self.options,
+            CallState::default(),
+        )
+        .await
+    }
+
+    /// Builds the request `call` would send to the backend, without sending it, e.g. to test the
+    /// effect of the proxy's settings on headers and URI.
+    ///
+    /// The request gets the same header stripping, forwarding headers and URI rewriting. Steps
+    /// applied around it in `call` are left out: the gate and async filter, request body limits
+    /// and the checks of `block_private_addresses`. `scheme` is the scheme the request arrived
+    /// with, as with an [`InboundScheme`] extension.
+    ///
+    /// ```
+    /// use hyper::{Body, Client, Request};
+    /// use hyper_reverse_proxy::ReverseProxy;
+    ///
+    /// let proxy = ReverseProxy::new(Client::new()).set_x_real_ip(true);
+    /// let request = Request::get("/users?page=2")
+    ///     .header("connection", "keep-alive")
+    ///     .header("x-forwarded-for", "10.0.0.1")
+    ///     .body(Body::empty())
+    ///     .unwrap();
+    ///
+    /// let proxied = proxy
+    ///     .build_proxied_request(
+    ///         "192.0.2.10".parse().unwrap(),
+    ///         "http://backend:8080/api",
+    ///         request,
+    ///         None,
+    ///     )
+    ///     .unwrap();
+    ///
+    /// assert_eq!(proxied.uri(), "http://backend:8080/api/users?page=2");
+    /// assert_eq!(proxied.headers()["x-forwarded-for"], "10.0.0.1, 192.0.2.10");
+    /// assert_eq!(proxied.headers()["x-real-ip"], "192.0.2.10");
+    /// assert!(!proxied.headers().contains_key("connection"));
+    /// ```
+    pub fn build_proxied_request<B>(
+        &self,
+        client_ip: IpAddr,
+        forward_uri: &str,
+        mut request: Request<B>,
+        scheme: Option<Scheme>,
+    ) -> Result<Request<B>, ProxyError> {
+        if let Some(scheme) = scheme {
+            request.extensions_mut().insert(InboundScheme(scheme));
+        }
+
+        let upgrade_type = get_upgrade_type(request.headers());
+
+        create_proxied_request(
+            client_ip,
+            forward_uri,
+            request,
+            upgrade_type.as_ref(),
+            &self.options,
+            None,
+        )
+    }
+
+    /// Reconstructs the URL the client requested, e.g. for access logs, from `scheme`, the `Host`
+    /// header and the request target. Unlike [`build_forward_uri`], this is not the backend URI.
+    ///
+    /// Returns `None` when the request has no valid `Host` header. HTTP/2 clients may send the
+    /// authority in the request URI instead, which is not used.
+    ///
+    /// ```
+    /// use hyper::{Body, Client, Request};
+    /// use hyper_reverse_proxy::ReverseProxy;
+    ///
+    /// let proxy = ReverseProxy::new(Client::new());
+    /// let request = Request::get("/users?page=2")
+    ///     .header("host", "example.com")
+    ///     .body(Body::empty())
+    ///     .unwrap();
+    ///
+    /// assert_eq!(
+    ///     proxy.effective_request_url("https", &request).as_deref(),
+    ///     Some("https://example.com/users?page=2")
+    /// );
+    /// ```
+    pub fn effective_request_url(&self, scheme: &str, request: &Request<Body>) -> Option<String> {
+        let host = request.headers().get(HOST)?.to_str().ok()?;
+        let path_and_query = request
+            .uri()
+            .path_and_query()
+            .map(|value| value.as_str())
+            .unwrap_or("/");
+
+        Some(format!("{}://{}{}", scheme, host, path_and_query))
+    }
+
+    /// Like `call`, but reads the whole response body into memory, e.g. to inspect or rewrite it.
+    ///
+    /// Fails with `ProxyError::ResponseBodyTooLarge` once the body exceeds `max_bytes`. Trailers
+    /// are dropped.
+    pub async fn call_buffered(
+        &self,
+        client_ip: IpAddr,
+        forward_uri: &str,
+        request: Request<Body>,
+        max_bytes: u64,
+    ) -> Result<Response<Bytes>, ProxyError> {
+        let (parts, body) = self
+            .call(client_ip, forward_uri, request)
+            .await?
+            .into_parts();
+        let body = buffer_body(body, max_bytes).await?;
+
+        Ok(Response::from_parts(parts, body))
+    }
+
+    /// Like `call`, but answers `GET` requests from the `with_response_cache` cache when possible.
+    ///
+    /// The gate and the async request filter run before the cache is consulted. The cache key is
+    /// the method and the URI the request is forwarded to, after `forward_absolute_form`, path
+    /// normalization and rewriting. On a miss, `200`
+    /// responses are stored for their `Cache-Control` `s-maxage` or `max-age`, unless marked
+    /// `no-store`, `no-cache` or `private`, or carrying `Vary` or `Set-Cookie`. Requests with
+    /// `Cache-Control: no-store` or `no-cache` bypass the cache. Only bodies with a
+    /// `Content-Length` of at most 1 MiB are stored, others are streamed as with `call`.
+    ///
+    /// As the key does not include credentials, requests carrying `Authorization` are only
+    /// answered from and stored in the cache when the response is `public` or has `s-maxage`
+    /// (RFC 7234 section 3.2). Responses that depend on cookies must say so with `Vary` or
+    /// `private` to stay out of the cache.
+    #[cfg(feature = "cache")]
+    pub async fn call_cached(
+        &self,
+        client_ip: IpAddr,
+        forward_uri: &str,
+        mut request: Request<Body>,
+    ) -> Result<Response<Body>, ProxyError> {
+        const MAX_CACHED_BODY: u64 = 1024 * 1024;
+
+        let cache = match &self.options.response_cache {
+            Some(cache)
+                if request.method() == Method::GET
+                    && cache::request_allows_cache(request.headers()) =>
+            {
+                cache.clone()
+            }
+            _ => return self.call(client_ip, forward_uri, request).await,
+        };
+
+        if let Some(rejected) = admit_request(&mut request, &self.options).await {
+            return rejected;
+        }
+
+        // only the URI, the version and the inbound scheme decide the target, the request itself is rewritten
+        // again when forwarded
+        let mut target = Request::new(());
+        *target.uri_mut() = request.uri().clone();
+        *target.version_mut() = request.version();
+        if let Some(scheme) = request.extensions().get::<InboundScheme>() {
+            target.extensions_mut().insert(scheme.clone());
+        }
+        let key = format!(
+            "{} {}",
+            request.method(),
+            proxied_uri(forward_uri, &mut target, &self.options)?
+        );
+
+        let authorized = request.headers().contains_key(AUTHORIZATION);
+
+        if let Some(cached) = cache.get(&key) {
+            if !authorized || cache::shared_with_authorization(&cached.headers) {
+                debug!("Answering {} from cache", key);
+
+                return Ok(cached.into_response());
+            }
+
+            debug!("Not sharing cached {} with an authorized request", key);
+        }
+
+        let response = call_with_options::<T>(
+            client_ip,
+            forward_uri,
+            request,
+            &self.client,
+            &self.options,
+            CallState {
+                admitted: true,
+                ..CallState::default()
+            },
+        )
+        .await?;
+        let content_length = response
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok());
+
+        let ttl = match cache::response_ttl(response.headers()) {
+            Some(ttl)
+                if response.status() == StatusCode::OK
+                    && (!authorized || cache::shared_with_authorization(response.headers()))
+                    && content_length
+                        .map(|length| length <= MAX_CACHED_BODY)
+                        .unwrap_or(false) =>
+            {
+                ttl
+            }
+            _ => return Ok(response),
+        };
+
+        let (parts, body) = response.into_parts();
+        let body = buffer_body(body, MAX_CACHED_BODY).await?;
+        let cached = CachedResponse {
+            status: parts.status,
+            headers: parts.headers,
+            body,
+        };
+        debug!("Caching {} for {:?}", key, ttl);
+        cache.put(&key, cached.clone(), ttl);
+
+        Ok(cached.into_response())
+    }
+
+    /// Like `call`, but aborts the call when `token` is cancelled.
+    ///
+    /// Cancelling before the backend answered fails the call with `ProxyError::Cancelled`. Later,
+    /// it aborts the response body still being streamed, or closes the connections of an upgraded
+    /// session (the `on_upgrade_complete` callback then receives an `Interrupted` error).
+    pub async fn call_with_cancel(
+        &self,
+        client_ip: IpAddr,
+        forward_uri: &str,
+        request: Request<Body>,
+        token: CancellationToken,
+    ) -> Result<Response<Body>, ProxyError> {
+        call_with_options::<T>(
+            client_ip,
+            forward_uri,
+            request,
+            &self.client,
+            &self.options,
+            CallState {
+                cancel: Some(&token),
+                ..CallState::default()
+            },
+        )
+        .await
+    }
+
+    /// Like `call`, but tells the backend about the TLS client certificate of the connection, for
+    /// proxies terminating mTLS.
+    ///
+    /// The subject and fingerprint are sent in `X-Client-Cert-Subject` and
+    /// `X-Client-Cert-Fingerprint`, or the headers set with `client_cert_headers`, replacing any
+    /// the client sent. Bytes outside printable ASCII and `%` are percent-encoded, an empty field
+    /// removes its header.
+    ///
+    /// Plain `call` forwards such headers from the client unchanged, strip them with
+    /// `header_policy` when only some calls carry a certificate.
+    pub async fn call_with_client_cert(
+        &self,
+        client_ip: IpAddr,
+        forward_uri: &str,
+        cert: &ClientCertInfo,
+        request: Request<Body>,
+    ) -> Result<Response<Body>, ProxyError> {
+        call_with_options::<T>(
+            client_ip,
+            forward_uri,
+            request,
+            &self.client,
+            &self.options,
+            CallState {
+                client_cert: Some(cert),
+                ..CallState::default()
+            },
+        )
+        .await
+    }
+
+    /// Like `call`, but also returns a [`ProxyTrace`] of what the proxy did with the request.
+    ///
+    /// Useful to diagnose a single request without enabling verbose logging.
+    pub async fn call_traced(
+        &self,
+        client_ip: IpAddr,
+        forward_uri: &str,
+        request: Request<Body>,
+    ) -> Result<(Response<Body>, ProxyTrace), ProxyError> {
+        let mut trace = ProxyTrace::default();
+        let response = call_with_options::<T>(
+            client_ip,
+            forward_uri,
+            request,
+            &self.client,
+            &self.options,
+            CallState {
+                trace: Some(&mut trace),
+                ..CallState::default()
+            },
+        )
+        .await?;
+
+        Ok((response, trace))
+    }
+
+    /// Like `call`, but also returns a [`ProxyTiming`] telling how long building the request and
+    /// waiting for the backend's response headers took.
+    pub async fn call_timed(
+        &self,
+        client_ip: IpAddr,
+        forward_uri: &str,
+        request: Request<Body>,
+    ) -> Result<(Response<Body>, ProxyTiming), ProxyError> {
+        let mut timing = ProxyTiming::default();
+        let response = call_with_options::<T>(
+            client_ip,
+            forward_uri,
+            request,
+            &self.client,
+            &self.options,
+            CallState {
+                timing: Some(&mut timing),
+                ..CallState::default()
+            },
+        )
+        .await?;
+
+        Ok((response, timing))
+    }
+
+    /// Like `call`, but connects to `addr` instead of resolving the host of `forward_uri`, e.g. to
+    /// pin a specific backend instance. The `Host` header is still taken from `forward_uri`.
+    ///
+    /// The connector only sees `addr`, so for `https` the TLS hostname is the IP as well, see
+    /// [`PinnedResolver`] for pinning an address while keeping the hostname.
+    pub async fn call_to_addr(
+        &self,
+        client_ip: IpAddr,
+        forward_uri: &str,
+        addr: SocketAddr,
+        request: Request<Body>,
+    ) -> Result<Response<Body>, ProxyError> {
+        let uri: Uri = forward_uri.parse()?;
+        let host = uri
+            .authority()
+            .ok_or_else(|| ProxyError::InvalidForwardUri {
+                uri: forward_uri.to_string(),
+                reason: "missing authority",
+            })?
+            .to_string();
+
+        let mut parts = uri.into_parts();
+        parts.authority = Some(addr.to_string().parse()?);
+        let addr_uri = Uri::from_parts(parts).expect("only the authority was changed");
+
+        call_with_options::<T>(
+            client_ip,
+            &addr_uri.to_string(),
+            request,
+            &self.client,
+            &self.options,
+            CallState {
+                host: Some(&host),
+                ..CallState::default()
+            },
+        )
+        .await
+    }
+
+    /// Like `call`, but forwards to one of `upstreams`, picked at random with a probability
+    /// proportional to its weight. Upstreams with weight `0` are never picked.
+    ///
+    /// When the picked upstream cannot be connected to or its circuit is open, the call fails over
+    /// to another one picked from the rest, until one answers or all failed. To be able to resend
+    /// it, the request body is buffered, up to `max_request_body` or 8 MiB when that is not set,
+    /// and extensions such as a pending upgrade only reach the first attempt.
+    pub async fn call_weighted(
+        &self,
+        client_ip: IpAddr,
+        upstreams: &[(&str, u32)],
+        request: Request<Body>,
+    ) -> Result<Response<Body>, ProxyError> {
+        let mut candidates: Vec<(&str, u32)> = upstreams
+            .iter()
+            .filter(|(_, weight)| *weight > 0)
+            .copied()
+            .collect();
+
+        if candidates.is_empty() {
+            return Err(ProxyError::NoUpstream);
+        }
+
+        const MAX_BUFFERED_BODY: u64 = 8 * 1024 * 1024;
+
+        let (mut parts, body) = request.into_parts();
+        let limit = self.options.max_request_body.unwrap_or(MAX_BUFFERED_BODY);
+        let body = buffer_body(body, limit).await.map_err(|err| match err {
+            ProxyError::ResponseBodyTooLarge => ProxyError::RequestBodyTooLarge,
+            err => err,
+        })?;
+
+        // a 503 with retry-after is only answered once no upstream is left
+        let failover_options = ProxyOptions {
+            unavailable_retry_after: None,
+            ..self.options.clone()
+        };
+        let mut extensions = Some(std::mem::take(&mut parts.extensions));
+
+        loop {
+            let (forward_uri, _) = candidates.swap_remove(pick_weighted(&candidates));
+            let mut request = Request::new(Body::from(body.clone()));
+            *request.method_mut() = parts.method.clone();
+            *request.uri_mut() = parts.uri.clone();
+            *request.version_mut() = parts.version;
+            *request.headers_mut() = parts.headers.clone();
+            if let Some(extensions) = extensions.take() {
+                *request.extensions_mut() = extensions;
+            }
+
+            let options = if candidates.is_empty() {
+                &self.options
+            } else {
+                &failover_options
+            };
+            let result = call_with_options::<T>(
+                client_ip,
+                forward_uri,
+                request,
+                &self.client,
+                options,
+                CallState::default(),
+            )
+            .await;
+
+            match result {
+                Err(ProxyError::ConnectError { uri, .. }) | Err(ProxyError::CircuitOpen(uri))
+                    if !candidates.is_empty() =>
+                {
+                    debug!("Upstream {} unreachable, failing over", uri);
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+impl<T> ReverseProxy<T>
+where
+    T: hyper::client::connect::Connect + Service<Uri> + Clone + Send + Sync + 'static,
+    T::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    /// Connects to `forward_uri` without sending a request, including the TLS handshake for
+    /// `https` when the connector supports it.
+    ///
+    /// Useful as a readiness check at startup. DNS, connect and TLS failures are returned as
+    /// `ProxyError::UpstreamUnavailable`. Requires the proxy to be created with `with_connector`.
+    pub async fn verify_upstream(&self, forward_uri: &str) -> Result<(), ProxyError> {
+        info!("Verifying upstream {}", forward_uri);
+
+        let uri: Uri = forward_uri.parse()?;
+        let mut connector = self.connector.clone().ok_or_else(|| {
+            ProxyError::UpstreamUnavailable("proxy was created without a connector".into())
+        })?;
+
+        futures_util::future::poll_fn(|cx| connector.poll_ready(cx))
+            .await
+            .map_err(|err| ProxyError::UpstreamUnavailable(err.into()))?;
+        connector
+            .call(uri)
+            .await
+            .map_err(|err| ProxyError::UpstreamUnavailable(err.into()))?;
+
+        debug!("Upstream {} is reachable", forward_uri);
+
+        Ok(())
+    }
+}
+
+impl ReverseProxy<BoxConnector> {
+    /// Like `with_connector`, but erases the connector type, so proxies using different
+    /// connectors share the type `ReverseProxy<BoxConnector>`. See [`BoxConnector`] for the cost.
+    pub fn boxed<C>(connector: C) -> Self
+    where
+        C: hyper::service::Service<Uri> + Clone + Send + Sync + 'static,
+        C::Response: tokio::io::AsyncRead
+            + tokio::io::AsyncWrite
+            + hyper::client::connect::Connection
+            + Send
+            + Unpin
+            + 'static,
+        C::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+        C::Future: Send + 'static,
+    {
+        Self::with_connector(BoxConnector::new(connector))
+    }
+}
+
+/// Builds a [`ReverseProxy`] using hyper's `HttpConnector`.
+///
+/// Either pass a pre-built `Client` for full control, or use the convenience toggles to configure
+/// the default client. The toggles are ignored when a client is set. Requires the `http` feature.
+#[cfg(feature = "http")]
+#[derive(Clone, Debug)]
+pub struct ReverseProxyBuilder {
+    client: Option<Client<HttpConnector>>,
+    client_builder: hyper::client::Builder,
+}
+
+#[cfg(feature = "http")]
+impl Default for ReverseProxyBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "http")]
+impl ReverseProxyBuilder {
+    pub fn new() -> Self {
+        Self {
+            client: None,
+            client_builder: Client::builder(),
+        }
+    }
+
+    /// Uses `client` instead of building one.
+    ///
+    /// hyper does not hand out the connector of a `Client`, so the built proxy has none and
+    /// `verify_upstream` fails, use `ReverseProxy::with_connector` to keep it.
+    pub fn client(mut self, client: Client<HttpConnector>) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Only speaks HTTP/2 with prior knowledge to the backend.
+    pub fn http2_only(mut self, enabled: bool) -> Self {
+        self.client_builder.http2_only(enabled);
+        self
+    }
+
+    /// Closes pooled connections after being idle for `timeout`.
+    pub fn pool_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.client_builder.pool_idle_timeout(timeout);
+        self
+    }
+
+    /// Keeps at most `max` idle connections per backend, `0` disables connection reuse.
+    pub fn pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.client_builder.pool_max_idle_per_host(max);
+        self
+    }
+
+    /// Builds the proxy. Only a client built from the toggles keeps its connector for
+    /// `verify_upstream`, not one passed to `client`.
+    pub fn build(self) -> ReverseProxy<HttpConnector> {
+        match self.client {
+            Some(client) => ReverseProxy::new(client),
+            None => {
+                let connector = HttpConnector::new();
+
+                ReverseProxy {
+                    client: self.client_builder.build(connector.clone()),
+                    connector: Some(connector),
+                    options: ProxyOptions::default(),
+                }
+            }
+        }
+    }
+}
+
+/// Forwards a request to a backend.
+///
+/// Implemented by [`ReverseProxy`], whose implementation is [`ReverseProxy::call`]. Code written
+/// against this trait can substitute a mock in tests instead of running a backend server.
+pub trait Forwarder {
+    /// Forwards `request` from `client_ip` to the backend at `forward_uri`.
+    fn forward<'a>(
+        &'a self,
+        client_ip: IpAddr,
+        forward_uri: &'a str,
+        request: Request<Body>,
+    ) -> BoxFuture<'a, Result<Response<Body>, ProxyError>>;
+}
+
+impl<T: hyper::client::connect::Connect + Clone + Send + Sync + 'static> Forwarder
+    for ReverseProxy<T>
+{
+    fn forward<'a>(
+        &'a self,
+        client_ip: IpAddr,
+        forward_uri: &'a str,
+        request: Request<Body>,
+    ) -> BoxFuture<'a, Result<Response<Body>, ProxyError>> {
+        Box::pin(self.call(client_ip, forward_uri, request))
     }
 }
 
@@ -476,8 +4493,20 @@ pub mod benches {
         &*super::HOP_HEADERS
     }
 
+    /// Removes the hop headers as the proxy does, in a single pass over small maps.
+    pub fn remove_hop_headers(headers: &mut crate::HeaderMap) {
+        super::remove_hop_headers(headers);
+    }
+
+    /// Removes the hop headers by looking each of them up in `headers`, for comparison.
+    pub fn remove_hop_headers_by_lookup(headers: &mut crate::HeaderMap) {
+        for header in &*super::HOP_HEADERS {
+            headers.remove(header);
+        }
+    }
+
     pub fn create_proxied_response<T>(response: crate::Response<T>) {
-        super::create_proxied_response(response);
+        super::create_proxied_response(response, &super::ProxyOptions::default(), None);
     }
 
     pub fn forward_uri<B>(forward_url: &str, req: &crate::Request<B>) {
@@ -490,6 +4519,14 @@ pub mod benches {
         request: crate::Request<B>,
         upgrade_type: Option<&String>,
     ) {
-        super::create_proxied_request(client_ip, forward_url, request, upgrade_type).unwrap();
+        super::create_proxied_request(
+            client_ip,
+            forward_url,
+            request,
+            upgrade_type,
+            &super::ProxyOptions::default(),
+            None,
+        )
+        .unwrap();
     }
 }
@@ -0,0 +1,41 @@
+//! Hooks for collecting proxy metrics, e.g. to export them to Prometheus.
+
+use crate::ProxyError;
+use hyper::StatusCode;
+
+/// Receives events from `ReverseProxy` calls.
+///
+/// All methods default to doing nothing, so implementations only override what they collect.
+/// Methods are called on the request path and should return quickly.
+pub trait ProxyMetrics: Send + Sync {
+    /// A call completed with a response of `status`, including upgrade responses.
+    fn record_request(&self, _status: StatusCode) {}
+
+    /// A call failed with `error`.
+    fn record_error(&self, _error: &ProxyError) {}
+
+    /// A connection upgrade (e.g. to a WebSocket) was negotiated.
+    fn record_upgrade(&self) {}
+}
+
+/// A `ProxyMetrics` implementation discarding every event.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoopMetrics;
+
+impl ProxyMetrics for NoopMetrics {}
+
+pub(crate) fn record_result(
+    metrics: &dyn ProxyMetrics,
+    result: &Result<hyper::Response<hyper::Body>, ProxyError>,
+) {
+    match result {
+        Ok(response) => {
+            if response.status() == StatusCode::SWITCHING_PROTOCOLS {
+                metrics.record_upgrade();
+            }
+
+            metrics.record_request(response.status());
+        }
+        Err(error) => metrics.record_error(error),
+    }
+}
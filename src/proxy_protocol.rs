@@ -0,0 +1,104 @@
+//! Reading the client address from a PROXY protocol header, as sent by L4 load balancers.
+//!
+//! See the [specification](https://www.haproxy.org/download/2.8/doc/proxy-protocol.txt).
+
+use hyper::body::Bytes;
+use std::convert::TryInto;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+
+/// The PROXY protocol header read off a connection, inserted into the extensions of its requests
+/// for `ClientIpSource::ProxyProtocol`.
+#[derive(Debug, Clone)]
+pub struct ProxyProtocolPreamble(pub Bytes);
+
+/// Returns the source address of the PROXY protocol v1 (text) or v2 (binary) header at the start
+/// of `preamble`. Bytes following the header are ignored.
+///
+/// `None` is returned for malformed or truncated headers and for connections that carry no
+/// client address: v1 `UNKNOWN`, v2 `LOCAL` (e.g. health checks) and v2 families other than
+/// IPv4 and IPv6.
+///
+/// Parsing the header off the connection is left to the server: read it from the accepted
+/// stream before handing the stream to hyper, and pass the result as `client_ip` to
+/// `ReverseProxy::call` in place of the peer address, which is the load balancer's. Alternatively
+/// insert the header as a [`ProxyProtocolPreamble`] and use `ClientIpSource::ProxyProtocol`.
+///
+/// ```
+/// use hyper_reverse_proxy::client_ip_from_proxy_protocol;
+///
+/// let preamble = b"PROXY TCP4 192.0.2.10 198.51.100.1 56324 443\r\nGET / HTTP/1.1\r\n";
+///
+/// assert_eq!(
+///     client_ip_from_proxy_protocol(preamble),
+///     Some("192.0.2.10".parse().unwrap())
+/// );
+/// ```
+pub fn client_ip_from_proxy_protocol(preamble: &[u8]) -> Option<IpAddr> {
+    if preamble.starts_with(&V2_SIGNATURE) {
+        parse_v2(&preamble[V2_SIGNATURE.len()..])
+    } else if preamble.starts_with(b"PROXY ") {
+        parse_v1(preamble)
+    } else {
+        None
+    }
+}
+
+/// The v1 header is a single line of at most 107 bytes, e.g.
+/// `PROXY TCP6 <src> <dst> <src port> <dst port>\r\n`.
+fn parse_v1(preamble: &[u8]) -> Option<IpAddr> {
+    let preamble = &preamble[..preamble.len().min(107)];
+    let end = preamble.windows(2).position(|bytes| bytes == b"\r\n")?;
+    let line = std::str::from_utf8(&preamble[..end]).ok()?;
+    let mut fields = line.split(' ');
+
+    let _proxy = fields.next()?;
+    let source = match (fields.next()?, fields.next()?) {
+        ("TCP4", source) => IpAddr::V4(source.parse::<Ipv4Addr>().ok()?),
+        ("TCP6", source) => IpAddr::V6(source.parse::<Ipv6Addr>().ok()?),
+        _ => return None,
+    };
+    let _destination = fields.next()?;
+    let _source_port = fields.next()?.parse::<u16>().ok()?;
+    let _destination_port = fields.next()?.parse::<u16>().ok()?;
+
+    match fields.next() {
+        Some(_) => None,
+        None => Some(source),
+    }
+}
+
+/// After the signature, the v2 header has a version and command byte, an address family and
+/// transport byte and the big-endian length of the address block that follows.
+fn parse_v2(header: &[u8]) -> Option<IpAddr> {
+    let (version_command, family, length) = match header {
+        [version_command, family, high, low, ..] => (
+            *version_command,
+            *family,
+            usize::from(u16::from_be_bytes([*high, *low])),
+        ),
+        _ => return None,
+    };
+    let addresses = header.get(4..4 + length)?;
+
+    // version 2 with the PROXY command, LOCAL connections were opened by the load balancer itself
+    if version_command != 0x21 {
+        return None;
+    }
+
+    // source and destination address followed by both ports
+    match family >> 4 {
+        0x1 if addresses.len() >= 12 => {
+            let source: [u8; 4] = addresses[..4].try_into().ok()?;
+
+            Some(IpAddr::V4(Ipv4Addr::from(source)))
+        }
+        0x2 if addresses.len() >= 36 => {
+            let source: [u8; 16] = addresses[..16].try_into().ok()?;
+
+            Some(IpAddr::V6(Ipv6Addr::from(source)))
+        }
+        _ => None,
+    }
+}
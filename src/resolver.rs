@@ -0,0 +1,88 @@
+//! A resolver pinning host names to fixed addresses, for reaching a backend by IP while TLS still
+//! presents its host name.
+
+use futures_util::future::BoxFuture;
+use hyper::client::connect::dns::{GaiResolver, Name};
+use hyper::service::Service;
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::vec;
+
+/// Resolves pinned host names to their addresses and all others with the system resolver.
+///
+/// A TLS connector takes the SNI server name from the host of the URI it connects to, and leaves
+/// resolving that host to the `HttpConnector` it wraps. Building that `HttpConnector` with a
+/// `PinnedResolver` connects to the pinned address, while the TLS handshake, certificate
+/// verification and the `Host` header still use the host name of the forward URI. The port is
+/// always taken from the forward URI.
+///
+/// ```
+/// use hyper::client::HttpConnector;
+/// use hyper_reverse_proxy::{PinnedResolver, ReverseProxy};
+///
+/// let resolver = PinnedResolver::new().pin("backend.example.com", "10.0.0.5".parse().unwrap());
+/// let mut connector = HttpConnector::new_with_resolver(resolver);
+/// connector.enforce_http(false);
+///
+/// // wrap `connector` in a TLS connector, e.g. `hyper_rustls::HttpsConnector`, then forward to
+/// // `https://backend.example.com`
+/// let proxy = ReverseProxy::with_connector(connector);
+/// ```
+#[derive(Debug, Clone)]
+pub struct PinnedResolver {
+    pins: Arc<HashMap<String, Vec<IpAddr>>>,
+    fallback: GaiResolver,
+}
+
+impl PinnedResolver {
+    /// Creates a resolver without pins, resolving every name with the system resolver.
+    pub fn new() -> Self {
+        Self {
+            pins: Arc::default(),
+            fallback: GaiResolver::new(),
+        }
+    }
+
+    /// Resolves `host` to `addr`, case-insensitively. Pinning a host more than once adds
+    /// addresses, which are tried in order.
+    pub fn pin(mut self, host: &str, addr: IpAddr) -> Self {
+        Arc::make_mut(&mut self.pins)
+            .entry(host.to_ascii_lowercase())
+            .or_default()
+            .push(addr);
+        self
+    }
+}
+
+impl Default for PinnedResolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Service<Name> for PinnedResolver {
+    type Response = vec::IntoIter<SocketAddr>;
+    type Error = io::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, io::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        self.fallback.poll_ready(cx)
+    }
+
+    fn call(&mut self, name: Name) -> Self::Future {
+        if let Some(addrs) = self.pins.get(&name.as_str().to_ascii_lowercase()) {
+            debug!("Resolving pinned host {} to {:?}", name, addrs);
+
+            // the connector replaces the port with the one of the URI
+            let addrs: Vec<SocketAddr> = addrs.iter().map(|ip| SocketAddr::new(*ip, 0)).collect();
+            return Box::pin(async move { Ok(addrs.into_iter()) });
+        }
+
+        let resolving = self.fallback.call(name);
+
+        Box::pin(async move { Ok(resolving.await?.collect::<Vec<_>>().into_iter()) })
+    }
+}
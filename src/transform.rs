@@ -0,0 +1,42 @@
+//! Reusable transformations of proxied bodies, see `ReverseProxy::with_body_transform`.
+
+use hyper::header::HeaderMap;
+use hyper::Body;
+
+/// Transforms request bodies on their way to the backend and response bodies on their way back,
+/// e.g. to compress or redact them.
+///
+/// Both methods receive the body as a stream of chunks and return the body to send instead,
+/// usually built with `Body::wrap_stream`. The headers are those about to be sent, so a
+/// transformer can e.g. set `Content-Encoding`. `Content-Length` is already removed, as the
+/// transformation may change the body length. Both methods default to passing the body through.
+///
+/// Chunk boundaries are decided by the connection the body arrives on, so a transformer must give
+/// the same result however the body is split.
+///
+/// ```
+/// use futures_util::StreamExt;
+/// use hyper::header::HeaderMap;
+/// use hyper::Body;
+/// use hyper_reverse_proxy::BodyTransform;
+///
+/// /// Uppercases ASCII response bodies.
+/// struct Uppercase;
+///
+/// impl BodyTransform for Uppercase {
+///     fn transform_response(&self, _headers: &mut HeaderMap, body: Body) -> Body {
+///         Body::wrap_stream(body.map(|chunk| chunk.map(|chunk| chunk.to_ascii_uppercase())))
+///     }
+/// }
+/// ```
+pub trait BodyTransform: Send + Sync {
+    /// Transforms the body of a request to the backend.
+    fn transform_request(&self, _headers: &mut HeaderMap, body: Body) -> Body {
+        body
+    }
+
+    /// Transforms the body of a response from the backend.
+    fn transform_response(&self, _headers: &mut HeaderMap, body: Body) -> Body {
+        body
+    }
+}
@@ -0,0 +1,129 @@
+#![allow(dead_code)]
+
+use hyper::client::connect::dns::GaiResolver;
+use hyper::client::HttpConnector;
+use hyper::server::conn::AddrStream;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Client, Request, Response, Server, StatusCode};
+use hyper_reverse_proxy::ReverseProxy;
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokiotest_httpserver::{take_port, HttpTestContext};
+
+pub type CapturedRequests = Arc<Mutex<Vec<Request<()>>>>;
+
+pub fn client_ip() -> IpAddr {
+    "127.0.0.1".parse().unwrap()
+}
+
+pub fn forward_url(ctx: &HttpTestContext) -> String {
+    format!("http://127.0.0.1:{}", ctx.port)
+}
+
+pub fn proxy() -> ReverseProxy<HttpConnector<GaiResolver>> {
+    ReverseProxy::new(Client::new())
+}
+
+/// Registers a backend handler answering `200 OK` and recording the request it received.
+pub fn capture_request(ctx: &mut HttpTestContext) -> CapturedRequests {
+    let captured: CapturedRequests = Arc::new(Mutex::new(Vec::new()));
+    capture_request_into(ctx, &captured);
+    captured
+}
+
+/// Like `capture_request`, recording into an existing list to capture several requests.
+pub fn capture_request_into(ctx: &mut HttpTestContext, captured: &CapturedRequests) {
+    let to_move = captured.clone();
+
+    ctx.add(Arc::new(move |req: Request<Body>| {
+        let (parts, _) = req.into_parts();
+        to_move.lock().unwrap().push(Request::from_parts(parts, ()));
+
+        Box::pin(async { Ok(Response::new(Body::empty())) })
+    }));
+}
+
+pub fn echo_body(ctx: &mut HttpTestContext) {
+    ctx.add(Arc::new(|req: Request<Body>| {
+        Box::pin(async move {
+            match hyper::body::to_bytes(req.into_body()).await {
+                Ok(body) => Ok(Response::new(Body::from(body))),
+                Err(_) => Ok(Response::builder()
+                    .status(StatusCode::BAD_REQUEST)
+                    .body(Body::empty())
+                    .unwrap()),
+            }
+        })
+    }));
+}
+
+pub fn streamed_body(chunks: usize) -> Body {
+    Body::wrap_stream(futures::stream::iter(
+        (0..chunks).map(|_| Ok::<_, std::io::Error>("0123456789")),
+    ))
+}
+
+/// Starts a backend answering a single connection with the raw `response` bytes, returning its
+/// port. Used for responses hyper's server would not produce.
+pub async fn raw_backend(response: &'static [u8]) -> u16 {
+    let port = take_port();
+    raw_backend_on(port, response).await;
+
+    port
+}
+
+/// Like `raw_backend`, on a given `port`, e.g. to bring up a backend that was unreachable.
+pub async fn raw_backend_on(port: u16, response: &'static [u8]) {
+    let listener = TcpListener::bind(("127.0.0.1", port)).await.unwrap();
+
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0u8; 1024];
+
+        while !request.ends_with(b"\r\n\r\n") {
+            let read = stream.read(&mut buf).await.unwrap();
+            if read == 0 {
+                return;
+            }
+            request.extend_from_slice(&buf[..read]);
+        }
+
+        stream.write_all(response).await.unwrap();
+        stream.shutdown().await.unwrap();
+    });
+}
+
+/// Serves `proxy` forwarding to `forward_url` on a new port, returning the port. Used where the
+/// client's own connection matters, e.g. for upgrades.
+pub fn serve_proxy(proxy: ReverseProxy<HttpConnector<GaiResolver>>, forward_url: String) -> u16 {
+    let make_svc = make_service_fn(move |conn: &AddrStream| {
+        let client_ip = conn.remote_addr().ip();
+        let proxy = proxy.clone();
+        let forward_url = forward_url.clone();
+
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
+                let proxy = proxy.clone();
+                let forward_url = forward_url.clone();
+
+                async move {
+                    let resp = proxy
+                        .call(client_ip, &forward_url, req)
+                        .await
+                        .unwrap_or_else(|err| panic!("did not expect error: {:?}", err));
+
+                    Ok::<Response<Body>, Infallible>(resp)
+                }
+            }))
+        }
+    });
+    let port = take_port();
+    let server = Server::bind(&SocketAddr::from(([127, 0, 0, 1], port))).serve(make_svc);
+    tokio::spawn(server);
+
+    port
+}
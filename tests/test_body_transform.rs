@@ -0,0 +1,125 @@
+use futures::StreamExt;
+use hyper::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_LENGTH, TRANSFER_ENCODING};
+use hyper::{Body, Request, Response};
+use hyper_reverse_proxy::BodyTransform;
+use std::sync::{Arc, Mutex};
+use test_context::test_context;
+use tokiotest_httpserver::HttpTestContext;
+
+mod common;
+
+use common::{client_ip, forward_url, proxy};
+
+/// Rotates ASCII letters by 13 places in both directions and marks the transformed messages.
+struct Rot13;
+
+impl Rot13 {
+    fn rotate(headers: &mut HeaderMap, body: Body, direction: &'static str) -> Body {
+        headers.insert(
+            HeaderName::from_static("x-rot13"),
+            HeaderValue::from_static(direction),
+        );
+
+        Body::wrap_stream(body.map(|chunk| {
+            chunk.map(|chunk| {
+                chunk
+                    .iter()
+                    .map(|byte| match byte {
+                        b'a'..=b'z' => (byte - b'a' + 13) % 26 + b'a',
+                        b'A'..=b'Z' => (byte - b'A' + 13) % 26 + b'A',
+                        _ => *byte,
+                    })
+                    .collect::<Vec<_>>()
+            })
+        }))
+    }
+}
+
+impl BodyTransform for Rot13 {
+    fn transform_request(&self, headers: &mut HeaderMap, body: Body) -> Body {
+        Self::rotate(headers, body, "request")
+    }
+
+    fn transform_response(&self, headers: &mut HeaderMap, body: Body) -> Body {
+        Self::rotate(headers, body, "response")
+    }
+}
+
+type Received = Arc<Mutex<Vec<(HeaderMap, String)>>>;
+
+/// Records the headers and body of the request and answers with `response_body`.
+fn recording_backend(ctx: &mut HttpTestContext, response_body: &'static str) -> Received {
+    let received: Received = Arc::new(Mutex::new(Vec::new()));
+    let to_move = received.clone();
+
+    ctx.add(Arc::new(move |req: Request<Body>| {
+        let received = to_move.clone();
+
+        Box::pin(async move {
+            let (parts, body) = req.into_parts();
+            let body = hyper::body::to_bytes(body).await.unwrap();
+            received
+                .lock()
+                .unwrap()
+                .push((parts.headers, String::from_utf8(body.to_vec()).unwrap()));
+
+            Ok(Response::new(Body::from(response_body)))
+        })
+    }));
+
+    received
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_body_transform_applies_in_both_directions(ctx: &mut HttpTestContext) {
+    let received = recording_backend(ctx, "Hello, client");
+
+    let resp = proxy()
+        .with_body_transform(Arc::new(Rot13))
+        .call(
+            client_ip(),
+            &forward_url(ctx),
+            Request::post("/")
+                .body(Body::from("Hello, backend"))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(resp.headers()["x-rot13"], "response");
+    assert!(!resp.headers().contains_key(CONTENT_LENGTH));
+    let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+    assert_eq!(body, "Uryyb, pyvrag");
+
+    let received = received.lock().unwrap();
+    let (headers, body) = &received[0];
+    assert_eq!(headers["x-rot13"], "request");
+    assert_eq!(body, "Uryyb, onpxraq");
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_body_transform_skips_empty_bodies(ctx: &mut HttpTestContext) {
+    let received = recording_backend(ctx, "Hello, client");
+
+    let resp = proxy()
+        .with_body_transform(Arc::new(Rot13))
+        .call(
+            client_ip(),
+            &forward_url(ctx),
+            Request::get("/").body(Body::empty()).unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+    assert_eq!(body, "Uryyb, pyvrag");
+
+    // an empty GET body must not turn into a chunked one
+    let received = received.lock().unwrap();
+    let (headers, body) = &received[0];
+    assert!(!headers.contains_key("x-rot13"));
+    assert!(!headers.contains_key(TRANSFER_ENCODING));
+    assert!(body.is_empty());
+}
@@ -0,0 +1,157 @@
+use futures::future::BoxFuture;
+use hyper::client::HttpConnector;
+use hyper::service::Service;
+use hyper::{Body, Client, Request, StatusCode, Uri, Version};
+use hyper_reverse_proxy::{BoxConnector, ProxyError, ReverseProxy, ReverseProxyBuilder};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use test_context::test_context;
+use tokio::net::TcpStream;
+use tokiotest_httpserver::HttpTestContext;
+
+mod common;
+
+use common::{capture_request, capture_request_into, client_ip, forward_url};
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_builder_http2_only(ctx: &mut HttpTestContext) {
+    let captured = capture_request(ctx);
+
+    let resp = ReverseProxyBuilder::new()
+        .http2_only(true)
+        .pool_idle_timeout(Duration::from_secs(30))
+        .build()
+        .call(
+            client_ip(),
+            &forward_url(ctx),
+            Request::get("/h2").body(Body::empty()).unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(captured.lock().unwrap()[0].version(), Version::HTTP_2);
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_builder_defaults_to_http1(ctx: &mut HttpTestContext) {
+    let captured = capture_request(ctx);
+
+    ReverseProxyBuilder::new()
+        .build()
+        .call(
+            client_ip(),
+            &forward_url(ctx),
+            Request::get("/h1").body(Body::empty()).unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(captured.lock().unwrap()[0].version(), Version::HTTP_11);
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_builder_uses_given_client(ctx: &mut HttpTestContext) {
+    let captured = capture_request(ctx);
+    let client = Client::builder().http2_only(true).build_http();
+
+    ReverseProxyBuilder::new()
+        .client(client)
+        .build()
+        .call(
+            client_ip(),
+            &forward_url(ctx),
+            Request::get("/client").body(Body::empty()).unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(captured.lock().unwrap()[0].version(), Version::HTTP_2);
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_builder_keeps_connector_only_of_built_client(ctx: &mut HttpTestContext) {
+    ReverseProxyBuilder::new()
+        .build()
+        .verify_upstream(&forward_url(ctx))
+        .await
+        .unwrap();
+
+    let result = ReverseProxyBuilder::new()
+        .client(Client::new())
+        .build()
+        .verify_upstream(&forward_url(ctx))
+        .await;
+
+    assert!(
+        matches!(result, Err(ProxyError::UpstreamUnavailable(_))),
+        "expected UpstreamUnavailable, got {:?}",
+        result
+    );
+}
+
+#[derive(Clone)]
+struct CountingConnector {
+    connects: Arc<AtomicUsize>,
+}
+
+impl Service<Uri> for CountingConnector {
+    type Response = TcpStream;
+    type Error = std::io::Error;
+    type Future = BoxFuture<'static, std::io::Result<TcpStream>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        self.connects.fetch_add(1, Ordering::SeqCst);
+        let port = uri.port_u16().unwrap();
+        Box::pin(TcpStream::connect(("127.0.0.1", port)))
+    }
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_boxed_proxies_share_a_type(ctx: &mut HttpTestContext) {
+    let captured = capture_request(ctx);
+    capture_request_into(ctx, &captured);
+    let connects = Arc::new(AtomicUsize::new(0));
+
+    let proxies: Vec<ReverseProxy<BoxConnector>> = vec![
+        ReverseProxy::boxed(HttpConnector::new()),
+        ReverseProxy::boxed(CountingConnector {
+            connects: connects.clone(),
+        }),
+    ];
+
+    for proxy in &proxies {
+        let resp = proxy
+            .call(
+                client_ip(),
+                &forward_url(ctx),
+                Request::get("/boxed").body(Body::empty()).unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    assert_eq!(captured.lock().unwrap().len(), 2);
+    assert_eq!(connects.load(Ordering::SeqCst), 1);
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_boxed_proxy_verifies_upstream(ctx: &mut HttpTestContext) {
+    let proxy = ReverseProxy::boxed(HttpConnector::new());
+
+    proxy.verify_upstream(&forward_url(ctx)).await.unwrap();
+}
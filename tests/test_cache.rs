@@ -0,0 +1,334 @@
+#![cfg(feature = "cache")]
+
+use hyper::header::{AUTHORIZATION, CACHE_CONTROL};
+use hyper::{Body, Request, Response, StatusCode};
+use hyper_reverse_proxy::{CachedResponse, LruCache, ResponseCache};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use test_context::test_context;
+use tokiotest_httpserver::HttpTestContext;
+
+mod common;
+
+use common::{client_ip, forward_url, proxy};
+
+/// Registers `calls` backend handlers answering with `cache_control`, returning how many
+/// requests reached the backend.
+fn counting_backend(
+    ctx: &mut HttpTestContext,
+    calls: usize,
+    cache_control: &'static str,
+) -> Arc<AtomicUsize> {
+    counting_backend_with(ctx, calls, cache_control, &[])
+}
+
+/// Like `counting_backend`, adding `headers` to every response.
+fn counting_backend_with(
+    ctx: &mut HttpTestContext,
+    calls: usize,
+    cache_control: &'static str,
+    headers: &'static [(&'static str, &'static str)],
+) -> Arc<AtomicUsize> {
+    let received = Arc::new(AtomicUsize::new(0));
+
+    for _ in 0..calls {
+        let received = received.clone();
+
+        ctx.add(Arc::new(move |_req: Request<Body>| {
+            let count = received.fetch_add(1, Ordering::SeqCst) + 1;
+
+            Box::pin(async move {
+                let mut response = Response::builder().header(CACHE_CONTROL, cache_control);
+                for (name, value) in headers {
+                    response = response.header(*name, *value);
+                }
+
+                Ok(response
+                    .body(Body::from(format!("response {}", count)))
+                    .unwrap())
+            })
+        }));
+    }
+
+    received
+}
+
+async fn get_twice(ctx: &mut HttpTestContext, request_cache_control: Option<&str>) -> Vec<String> {
+    match request_cache_control {
+        Some(cache_control) => {
+            let headers = [(CACHE_CONTROL.as_str(), cache_control)];
+            get_with(ctx, &[&headers, &headers]).await
+        }
+        None => get_with(ctx, &[&[], &[]]).await,
+    }
+}
+
+/// Sends one cached request per entry of `requests`, each with the given headers, returning the
+/// response bodies.
+async fn get_with(ctx: &mut HttpTestContext, requests: &[&[(&str, &str)]]) -> Vec<String> {
+    let proxy = proxy().with_response_cache(Arc::new(LruCache::new(16)));
+    let mut bodies = Vec::new();
+
+    for headers in requests {
+        let mut request = Request::get("/cached?a=1");
+        for (name, value) in *headers {
+            request = request.header(*name, *value);
+        }
+
+        let resp = proxy
+            .call_cached(
+                client_ip(),
+                &forward_url(ctx),
+                request.body(Body::empty()).unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        bodies.push(String::from_utf8(body.to_vec()).unwrap());
+    }
+
+    bodies
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_call_cached_hit_after_miss(ctx: &mut HttpTestContext) {
+    let received = counting_backend(ctx, 2, "public, max-age=60");
+
+    assert_eq!(get_twice(ctx, None).await, ["response 1", "response 1"]);
+    assert_eq!(received.load(Ordering::SeqCst), 1);
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_call_cached_skips_non_cacheable_responses(ctx: &mut HttpTestContext) {
+    let received = counting_backend(ctx, 2, "max-age=60, no-store");
+
+    assert_eq!(get_twice(ctx, None).await, ["response 1", "response 2"]);
+    assert_eq!(received.load(Ordering::SeqCst), 2);
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_call_cached_skips_responses_without_lifetime(ctx: &mut HttpTestContext) {
+    let received = counting_backend(ctx, 2, "public");
+
+    assert_eq!(get_twice(ctx, None).await, ["response 1", "response 2"]);
+    assert_eq!(received.load(Ordering::SeqCst), 2);
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_call_cached_bypassed_by_request_no_cache(ctx: &mut HttpTestContext) {
+    let received = counting_backend(ctx, 2, "max-age=60");
+
+    assert_eq!(
+        get_twice(ctx, Some("no-cache")).await,
+        ["response 1", "response 2"]
+    );
+    assert_eq!(received.load(Ordering::SeqCst), 2);
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_call_cached_ignores_other_methods(ctx: &mut HttpTestContext) {
+    let received = counting_backend(ctx, 2, "max-age=60");
+    let proxy = proxy().with_response_cache(Arc::new(LruCache::new(16)));
+
+    for _ in 0..2 {
+        proxy
+            .call_cached(
+                client_ip(),
+                &forward_url(ctx),
+                Request::post("/cached").body(Body::empty()).unwrap(),
+            )
+            .await
+            .unwrap();
+    }
+
+    assert_eq!(received.load(Ordering::SeqCst), 2);
+}
+
+fn cached(body: &'static str) -> CachedResponse {
+    CachedResponse {
+        status: StatusCode::OK,
+        headers: Default::default(),
+        body: body.into(),
+    }
+}
+
+#[test]
+fn test_lru_cache_evicts_least_recently_used() {
+    let cache = LruCache::new(2);
+    let ttl = Duration::from_secs(60);
+    cache.put("a", cached("a"), ttl);
+    cache.put("b", cached("b"), ttl);
+
+    // touching a leaves b as the least recently used entry
+    assert!(cache.get("a").is_some());
+    cache.put("c", cached("c"), ttl);
+
+    assert!(cache.get("a").is_some());
+    assert!(cache.get("b").is_none());
+    assert!(cache.get("c").is_some());
+}
+
+#[test]
+fn test_lru_cache_expires_entries() {
+    let cache = LruCache::new(2);
+    cache.put("a", cached("a"), Duration::from_millis(0));
+
+    assert!(cache.get("a").is_none());
+}
+
+const AUTHORIZED: &[(&str, &str)] = &[("authorization", "Bearer alice")];
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_call_cached_skips_authorized_requests(ctx: &mut HttpTestContext) {
+    let received = counting_backend(ctx, 2, "max-age=60");
+
+    assert_eq!(
+        get_with(ctx, &[AUTHORIZED, AUTHORIZED]).await,
+        ["response 1", "response 2"]
+    );
+    assert_eq!(received.load(Ordering::SeqCst), 2);
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_call_cached_does_not_answer_authorized_requests_from_private_entries(
+    ctx: &mut HttpTestContext,
+) {
+    let received = counting_backend(ctx, 2, "max-age=60");
+
+    // the anonymous response is cached, but must not be handed to a request with credentials
+    assert_eq!(
+        get_with(ctx, &[&[], AUTHORIZED]).await,
+        ["response 1", "response 2"]
+    );
+    assert_eq!(received.load(Ordering::SeqCst), 2);
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_call_cached_shares_public_authorized_responses(ctx: &mut HttpTestContext) {
+    let received = counting_backend(ctx, 4, "public, max-age=60");
+
+    assert_eq!(
+        get_with(ctx, &[AUTHORIZED, AUTHORIZED]).await,
+        ["response 1", "response 1"]
+    );
+    assert_eq!(received.load(Ordering::SeqCst), 1);
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_call_cached_shares_s_maxage_authorized_responses(ctx: &mut HttpTestContext) {
+    let received = counting_backend(ctx, 2, "s-maxage=60");
+
+    assert_eq!(
+        get_with(
+            ctx,
+            &[AUTHORIZED, &[(AUTHORIZATION.as_str(), "Bearer bob")]]
+        )
+        .await,
+        ["response 1", "response 1"]
+    );
+    assert_eq!(received.load(Ordering::SeqCst), 1);
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_call_cached_skips_responses_with_vary(ctx: &mut HttpTestContext) {
+    let received =
+        counting_backend_with(ctx, 2, "public, max-age=60", &[("vary", "accept-language")]);
+
+    assert_eq!(get_twice(ctx, None).await, ["response 1", "response 2"]);
+    assert_eq!(received.load(Ordering::SeqCst), 2);
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_call_cached_skips_responses_setting_cookies(ctx: &mut HttpTestContext) {
+    let received = counting_backend_with(
+        ctx,
+        2,
+        "public, max-age=60",
+        &[("set-cookie", "session=alice")],
+    );
+
+    assert_eq!(get_twice(ctx, None).await, ["response 1", "response 2"]);
+    assert_eq!(received.load(Ordering::SeqCst), 2);
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_call_cached_keys_absolute_form_by_target(ctx: &mut HttpTestContext) {
+    let received = counting_backend(ctx, 2, "public, max-age=60");
+    let proxy = proxy()
+        .forward_absolute_form(true)
+        .with_response_cache(Arc::new(LruCache::new(16)));
+    let port = ctx.port;
+    let mut bodies = Vec::new();
+
+    for host in ["127.0.0.1", "localhost"] {
+        let request = Request::get(format!("http://{}:{}/cached", host, port))
+            .body(Body::empty())
+            .unwrap();
+        let resp = proxy
+            .call_cached(client_ip(), "http://unused.example", request)
+            .await
+            .unwrap();
+
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        bodies.push(String::from_utf8(body.to_vec()).unwrap());
+    }
+
+    assert_eq!(bodies, ["response 1", "response 2"]);
+    assert_eq!(received.load(Ordering::SeqCst), 2);
+}
+
+#[allow(clippy::result_large_err)]
+fn deny_blocked() -> impl Fn(&Request<Body>) -> Result<(), Response<Body>> {
+    |request| {
+        if request.headers().contains_key("x-blocked") {
+            Err(Response::builder()
+                .status(StatusCode::FORBIDDEN)
+                .body(Body::empty())
+                .unwrap())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_call_cached_hit_runs_the_gate(ctx: &mut HttpTestContext) {
+    counting_backend(ctx, 1, "public, max-age=60");
+    let proxy = proxy()
+        .with_gate(deny_blocked())
+        .with_response_cache(Arc::new(LruCache::new(16)));
+
+    for (blocked, status) in [(false, StatusCode::OK), (true, StatusCode::FORBIDDEN)] {
+        let mut request = Request::get("/cached");
+        if blocked {
+            request = request.header("x-blocked", "1");
+        }
+
+        let resp = proxy
+            .call_cached(
+                client_ip(),
+                &forward_url(ctx),
+                request.body(Body::empty()).unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(resp.status(), status);
+    }
+}
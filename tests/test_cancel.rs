@@ -0,0 +1,108 @@
+use hyper::body::HttpBody;
+use hyper::{Body, Request, Response};
+use hyper_reverse_proxy::{CancellationToken, ProxyError};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use test_context::test_context;
+use tokio::net::TcpListener;
+use tokio::time::timeout;
+use tokiotest_httpserver::{take_port, HttpTestContext};
+
+mod common;
+
+use common::{client_ip, forward_url, proxy};
+
+/// Sets the flag when the backend drops its response stream.
+struct DropFlag(Arc<AtomicBool>);
+
+impl Drop for DropFlag {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_cancel_aborts_streamed_response(ctx: &mut HttpTestContext) {
+    let dropped = Arc::new(AtomicBool::new(false));
+    let dropped_to_move = dropped.clone();
+    ctx.add(Arc::new(move |_req: Request<Body>| {
+        let flag = DropFlag(dropped_to_move.clone());
+        let events = futures::stream::unfold(flag, |flag| async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Some((Ok::<_, std::io::Error>("data: tick\n\n"), flag))
+        });
+
+        Box::pin(async move { Ok(Response::new(Body::wrap_stream(events))) })
+    }));
+
+    let token = CancellationToken::new();
+    let resp = proxy()
+        .call_with_cancel(
+            client_ip(),
+            &forward_url(ctx),
+            Request::get("/events").body(Body::empty()).unwrap(),
+            token.clone(),
+        )
+        .await
+        .unwrap();
+    let mut body = resp.into_body();
+
+    assert!(body.data().await.unwrap().is_ok());
+    token.cancel();
+
+    let rest = timeout(Duration::from_secs(1), async {
+        while let Some(chunk) = body.data().await {
+            chunk?;
+        }
+        Ok::<_, hyper::Error>(())
+    })
+    .await
+    .expect("body did not end after cancel");
+    assert!(rest.is_err());
+
+    timeout(Duration::from_secs(2), async {
+        while !dropped.load(Ordering::SeqCst) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("backend stream still running after cancel");
+}
+
+#[tokio::test]
+async fn test_cancel_before_response() {
+    let port = take_port();
+    let listener = TcpListener::bind(("127.0.0.1", port)).await.unwrap();
+    tokio::spawn(async move {
+        // accept, but never answer
+        let (_stream, _) = listener.accept().await.unwrap();
+        tokio::time::sleep(Duration::from_secs(60)).await;
+    });
+
+    let token = CancellationToken::new();
+    let cancel = token.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        cancel.cancel();
+    });
+
+    let result = timeout(
+        Duration::from_secs(1),
+        proxy().call_with_cancel(
+            client_ip(),
+            &format!("http://127.0.0.1:{}", port),
+            Request::get("/").body(Body::empty()).unwrap(),
+            token,
+        ),
+    )
+    .await
+    .expect("call did not end after cancel");
+
+    assert!(
+        matches!(result, Err(ProxyError::Cancelled)),
+        "expected Cancelled, got {:?}",
+        result
+    );
+}
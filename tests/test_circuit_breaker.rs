@@ -0,0 +1,154 @@
+use hyper::{Body, Request, Response, StatusCode};
+use hyper_reverse_proxy::ProxyError;
+use std::time::Duration;
+use test_context::test_context;
+use tokiotest_httpserver::{take_port, HttpTestContext};
+
+mod common;
+
+use common::{capture_request, client_ip, forward_url, proxy, raw_backend_on};
+
+fn get() -> Request<Body> {
+    Request::get("/").body(Body::empty()).unwrap()
+}
+
+#[tokio::test]
+async fn test_circuit_opens_after_consecutive_failures() {
+    let forward_url = format!("http://127.0.0.1:{}", take_port());
+    let proxy = proxy().circuit_breaker(2, Duration::from_secs(60));
+
+    for _ in 0..2 {
+        let result = proxy.call(client_ip(), &forward_url, get()).await;
+        assert!(
+            matches!(result, Err(ProxyError::ConnectError { .. })),
+            "expected ConnectError, got {:?}",
+            result
+        );
+    }
+
+    let result = proxy.call(client_ip(), &forward_url, get()).await;
+    assert!(
+        matches!(&result, Err(ProxyError::CircuitOpen(uri)) if *uri == forward_url),
+        "expected CircuitOpen, got {:?}",
+        result
+    );
+    assert_eq!(
+        result.unwrap_err().status(),
+        StatusCode::SERVICE_UNAVAILABLE
+    );
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_circuits_are_per_forward_uri(ctx: &mut HttpTestContext) {
+    let captured = capture_request(ctx);
+    let unreachable = format!("http://127.0.0.1:{}", take_port());
+    let proxy = proxy().circuit_breaker(1, Duration::from_secs(60));
+
+    assert!(proxy.call(client_ip(), &unreachable, get()).await.is_err());
+
+    let resp = proxy
+        .call(client_ip(), &forward_url(ctx), get())
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(captured.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_circuit_half_opens_after_cooldown() {
+    let port = take_port();
+    let forward_url = format!("http://127.0.0.1:{}", port);
+    let proxy = proxy().circuit_breaker(1, Duration::from_millis(200));
+
+    assert!(proxy.call(client_ip(), &forward_url, get()).await.is_err());
+    let result = proxy.call(client_ip(), &forward_url, get()).await;
+    assert!(
+        matches!(result, Err(ProxyError::CircuitOpen(_))),
+        "expected CircuitOpen, got {:?}",
+        result
+    );
+
+    // still unreachable after the cooldown, a single failure reopens the circuit
+    tokio::time::sleep(Duration::from_millis(250)).await;
+    let result = proxy.call(client_ip(), &forward_url, get()).await;
+    assert!(
+        matches!(result, Err(ProxyError::ConnectError { .. })),
+        "expected ConnectError, got {:?}",
+        result
+    );
+    let result = proxy.call(client_ip(), &forward_url, get()).await;
+    assert!(
+        matches!(result, Err(ProxyError::CircuitOpen(_))),
+        "expected CircuitOpen, got {:?}",
+        result
+    );
+
+    // recovered after the next cooldown
+    tokio::time::sleep(Duration::from_millis(250)).await;
+    raw_backend_on(port, b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n").await;
+    let resp = proxy.call(client_ip(), &forward_url, get()).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+/// Rejects requests to `/rejected` with `405 Method Not Allowed`.
+#[allow(clippy::result_large_err)]
+fn rejecting_gate() -> impl Fn(&Request<Body>) -> Result<(), Response<Body>> {
+    |request| {
+        if request.uri().path() == "/rejected" {
+            Err(Response::builder()
+                .status(StatusCode::METHOD_NOT_ALLOWED)
+                .body(Body::empty())
+                .unwrap())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_gate_runs_before_open_circuit() {
+    let forward_url = format!("http://127.0.0.1:{}", take_port());
+    let proxy = proxy()
+        .circuit_breaker(1, Duration::from_secs(60))
+        .with_gate(rejecting_gate());
+
+    assert!(proxy.call(client_ip(), &forward_url, get()).await.is_err());
+
+    let resp = proxy
+        .call(
+            client_ip(),
+            &forward_url,
+            Request::get("/rejected").body(Body::empty()).unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+}
+
+#[tokio::test]
+async fn test_gate_rejections_do_not_close_the_circuit() {
+    let forward_url = format!("http://127.0.0.1:{}", take_port());
+    let proxy = proxy()
+        .circuit_breaker(2, Duration::from_secs(60))
+        .with_gate(rejecting_gate());
+
+    assert!(proxy.call(client_ip(), &forward_url, get()).await.is_err());
+    let resp = proxy
+        .call(
+            client_ip(),
+            &forward_url,
+            Request::get("/rejected").body(Body::empty()).unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert!(proxy.call(client_ip(), &forward_url, get()).await.is_err());
+
+    let result = proxy.call(client_ip(), &forward_url, get()).await;
+    assert!(
+        matches!(result, Err(ProxyError::CircuitOpen(_))),
+        "expected CircuitOpen, got {:?}",
+        result
+    );
+}
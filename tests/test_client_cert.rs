@@ -0,0 +1,103 @@
+use hyper::header::{HeaderName, CONNECTION};
+use hyper::{Body, Request};
+use hyper_reverse_proxy::ClientCertInfo;
+use test_context::test_context;
+use tokiotest_httpserver::HttpTestContext;
+
+mod common;
+
+use common::{capture_request, client_ip, forward_url, proxy};
+
+fn cert(subject: &str, fingerprint: &str) -> ClientCertInfo {
+    ClientCertInfo {
+        subject: subject.to_string(),
+        fingerprint: fingerprint.to_string(),
+    }
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_client_cert_headers_from_cert_info(ctx: &mut HttpTestContext) {
+    let captured = capture_request(ctx);
+    let request = Request::get("/")
+        .header("x-client-cert-subject", "CN=spoofed")
+        .body(Body::empty())
+        .unwrap();
+
+    proxy()
+        .call_with_client_cert(
+            client_ip(),
+            &forward_url(ctx),
+            &cert("CN=client,O=Example", "ab12cd34"),
+            request,
+        )
+        .await
+        .unwrap();
+
+    let captured = captured.lock().unwrap();
+    let headers = captured[0].headers();
+    assert_eq!(
+        headers
+            .get_all("x-client-cert-subject")
+            .iter()
+            .collect::<Vec<_>>(),
+        ["CN=client,O=Example"]
+    );
+    assert_eq!(headers["x-client-cert-fingerprint"], "ab12cd34");
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_client_cert_values_are_header_safe(ctx: &mut HttpTestContext) {
+    let captured = capture_request(ctx);
+
+    proxy()
+        .call_with_client_cert(
+            client_ip(),
+            &forward_url(ctx),
+            &cert("CN=Jürgen\r\nX-Admin: 1,O=100%", "ab12"),
+            Request::get("/").body(Body::empty()).unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let captured = captured.lock().unwrap();
+    assert_eq!(
+        captured[0].headers()["x-client-cert-subject"],
+        "CN=J%C3%BCrgen%0D%0AX-Admin: 1,O=100%25"
+    );
+    assert!(!captured[0].headers().contains_key("x-admin"));
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_client_cert_custom_headers(ctx: &mut HttpTestContext) {
+    let captured = capture_request(ctx);
+    // a client listing the header as connection-specific must not strip it
+    let request = Request::get("/")
+        .header(CONNECTION, "ssl-client-subject")
+        .header("ssl-client-fingerprint", "spoofed")
+        .body(Body::empty())
+        .unwrap();
+
+    proxy()
+        .client_cert_headers(
+            HeaderName::from_static("ssl-client-subject"),
+            HeaderName::from_static("ssl-client-fingerprint"),
+        )
+        .call_with_client_cert(
+            client_ip(),
+            &forward_url(ctx),
+            &cert("CN=client", ""),
+            request,
+        )
+        .await
+        .unwrap();
+
+    let captured = captured.lock().unwrap();
+    let headers = captured[0].headers();
+    assert_eq!(headers["ssl-client-subject"], "CN=client");
+    // an empty field removes the header instead of passing the client's value on
+    assert!(!headers.contains_key("ssl-client-fingerprint"));
+    assert!(!headers.contains_key("x-client-cert-subject"));
+}
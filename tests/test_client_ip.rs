@@ -0,0 +1,209 @@
+use hyper::body::Bytes;
+use hyper::{Body, Request};
+use hyper_reverse_proxy::{ClientIpSource, ProxyProtocolPreamble};
+use test_context::test_context;
+use tokiotest_httpserver::HttpTestContext;
+
+mod common;
+
+use common::{capture_request, capture_request_into, forward_url, proxy};
+
+/// Proxies `request` from `peer` resolving the client with `source`, returns the `X-Real-IP` and
+/// `X-Forwarded-For` the backend received.
+async fn resolved(
+    ctx: &mut HttpTestContext,
+    source: ClientIpSource,
+    peer: &str,
+    request: Request<Body>,
+) -> (String, String) {
+    let captured = capture_request(ctx);
+
+    proxy()
+        .trusted_proxies(vec![
+            "127.0.0.0/8".parse().unwrap(),
+            "10.1.0.0/16".parse().unwrap(),
+        ])
+        .set_x_real_ip(true)
+        .client_ip_source(source)
+        .call(peer.parse().unwrap(), &forward_url(ctx), request)
+        .await
+        .unwrap();
+
+    let captured = captured.lock().unwrap();
+    let header = |name: &str| captured[0].headers()[name].to_str().unwrap().to_string();
+    (header("x-real-ip"), header("x-forwarded-for"))
+}
+
+fn forwarded_for(chain: &str) -> Request<Body> {
+    Request::get("/")
+        .header("x-forwarded-for", chain)
+        .body(Body::empty())
+        .unwrap()
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_socket_peer_by_default(ctx: &mut HttpTestContext) {
+    let (real_ip, forwarded_for) = resolved(
+        ctx,
+        ClientIpSource::default(),
+        "127.0.0.1",
+        forwarded_for("203.0.113.7"),
+    )
+    .await;
+
+    assert_eq!(real_ip, "127.0.0.1");
+    assert_eq!(forwarded_for, "203.0.113.7, 127.0.0.1");
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_leftmost_forwarded_for(ctx: &mut HttpTestContext) {
+    let (real_ip, forwarded_for) = resolved(
+        ctx,
+        ClientIpSource::LeftmostForwardedFor,
+        "127.0.0.1",
+        forwarded_for("198.51.100.1, 203.0.113.7, 10.1.0.1"),
+    )
+    .await;
+
+    assert_eq!(real_ip, "198.51.100.1");
+    // the chain is kept and extended on behalf of the trusted peer
+    assert_eq!(
+        forwarded_for,
+        "198.51.100.1, 203.0.113.7, 10.1.0.1, 127.0.0.1"
+    );
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_leftmost_forwarded_for_from_untrusted_peer(ctx: &mut HttpTestContext) {
+    let (real_ip, forwarded_for) = resolved(
+        ctx,
+        ClientIpSource::LeftmostForwardedFor,
+        "192.0.2.1",
+        forwarded_for("198.51.100.1"),
+    )
+    .await;
+
+    assert_eq!(real_ip, "192.0.2.1");
+    assert_eq!(forwarded_for, "192.0.2.1");
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_rightmost_untrusted_forwarded_for(ctx: &mut HttpTestContext) {
+    // the client prepended 198.51.100.1 itself, only the trusted hops are believed
+    let (real_ip, forwarded_for) = resolved(
+        ctx,
+        ClientIpSource::RightmostUntrustedForwardedFor,
+        "127.0.0.1",
+        forwarded_for("198.51.100.1, 203.0.113.7, 10.1.0.1"),
+    )
+    .await;
+
+    assert_eq!(real_ip, "203.0.113.7");
+    assert_eq!(
+        forwarded_for,
+        "198.51.100.1, 203.0.113.7, 10.1.0.1, 127.0.0.1"
+    );
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_rightmost_untrusted_forwarded_for_stops_at_unknown(ctx: &mut HttpTestContext) {
+    let (real_ip, _) = resolved(
+        ctx,
+        ClientIpSource::RightmostUntrustedForwardedFor,
+        "127.0.0.1",
+        forwarded_for("203.0.113.7, unknown, 10.1.0.1"),
+    )
+    .await;
+
+    assert_eq!(real_ip, "10.1.0.1");
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_proxy_protocol(ctx: &mut HttpTestContext) {
+    let mut request = forwarded_for("198.51.100.1");
+    request
+        .extensions_mut()
+        .insert(ProxyProtocolPreamble(Bytes::from_static(
+            b"PROXY TCP4 192.0.2.10 198.51.100.20 56324 443\r\n",
+        )));
+
+    let (real_ip, forwarded_for) =
+        resolved(ctx, ClientIpSource::ProxyProtocol, "127.0.0.1", request).await;
+
+    assert_eq!(real_ip, "192.0.2.10");
+    assert_eq!(forwarded_for, "198.51.100.1, 127.0.0.1");
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_proxy_protocol_falls_back_to_peer(ctx: &mut HttpTestContext) {
+    let (real_ip, _) = resolved(
+        ctx,
+        ClientIpSource::ProxyProtocol,
+        "127.0.0.1",
+        forwarded_for("198.51.100.1"),
+    )
+    .await;
+
+    assert_eq!(real_ip, "127.0.0.1");
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_resolved_client_is_not_appended_to_forwarded_for(ctx: &mut HttpTestContext) {
+    let captured = capture_request(ctx);
+
+    // the trusted peer is the address appended, not the client it vouches for
+    proxy()
+        .trusted_proxies(vec!["9.9.9.9/32".parse().unwrap()])
+        .set_x_real_ip(true)
+        .client_ip_source(ClientIpSource::LeftmostForwardedFor)
+        .call(
+            "9.9.9.9".parse().unwrap(),
+            &forward_url(ctx),
+            forwarded_for("1.1.1.1, 2.2.2.2"),
+        )
+        .await
+        .unwrap();
+
+    let captured = captured.lock().unwrap();
+    let headers = captured[0].headers();
+    assert_eq!(headers["x-forwarded-for"], "1.1.1.1, 2.2.2.2, 9.9.9.9");
+    assert_eq!(headers["x-real-ip"], "1.1.1.1");
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_forwarded_for_sources_trust_no_peer_by_default(ctx: &mut HttpTestContext) {
+    let captured = capture_request(ctx);
+    for _ in 1..2 {
+        capture_request_into(ctx, &captured);
+    }
+
+    for source in [
+        ClientIpSource::LeftmostForwardedFor,
+        ClientIpSource::RightmostUntrustedForwardedFor,
+    ] {
+        // without trusted proxies a client must not pick its own address
+        proxy()
+            .set_x_real_ip(true)
+            .client_ip_source(source)
+            .call(
+                "203.0.113.9".parse().unwrap(),
+                &forward_url(ctx),
+                forwarded_for("6.6.6.6"),
+            )
+            .await
+            .unwrap();
+    }
+
+    for request in captured.lock().unwrap().iter() {
+        assert_eq!(request.headers()["x-real-ip"], "203.0.113.9");
+    }
+}
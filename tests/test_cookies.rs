@@ -0,0 +1,132 @@
+use hyper::header::{COOKIE, SET_COOKIE};
+use hyper::{Body, Request, Response};
+use std::sync::Arc;
+use test_context::test_context;
+use tokiotest_httpserver::HttpTestContext;
+
+mod common;
+
+use common::{capture_request, client_ip, forward_url, proxy};
+
+async fn proxied_set_cookies(
+    ctx: &mut HttpTestContext,
+    cookies: &'static [&'static str],
+) -> Vec<String> {
+    ctx.add(Arc::new(move |_req: Request<Body>| {
+        Box::pin(async move {
+            let mut response = Response::builder();
+
+            for cookie in cookies {
+                response = response.header(SET_COOKIE, *cookie);
+            }
+
+            Ok(response.body(Body::empty()).unwrap())
+        })
+    }));
+
+    let resp = proxy()
+        .rewrite_cookie_domain("backend.internal".to_string(), "example.com".to_string())
+        .call(
+            client_ip(),
+            &forward_url(ctx),
+            Request::get("/").body(Body::empty()).unwrap(),
+        )
+        .await
+        .unwrap();
+
+    resp.headers()
+        .get_all(SET_COOKIE)
+        .iter()
+        .map(|value| value.to_str().unwrap().to_string())
+        .collect()
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_cookie_domain_rewritten(ctx: &mut HttpTestContext) {
+    assert_eq!(
+        proxied_set_cookies(ctx, &["id=a3fWa; Domain=.Backend.Internal; Path=/; Secure"]).await,
+        ["id=a3fWa; Domain=example.com; Path=/; Secure"]
+    );
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_cookie_without_domain_untouched(ctx: &mut HttpTestContext) {
+    assert_eq!(
+        proxied_set_cookies(ctx, &["domain=backend.internal;Path=/"]).await,
+        ["domain=backend.internal;Path=/"]
+    );
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_multiple_cookies_rewritten(ctx: &mut HttpTestContext) {
+    assert_eq!(
+        proxied_set_cookies(
+            ctx,
+            &[
+                "id=a3fWa; Domain=backend.internal",
+                "theme=dark; Domain=other.org",
+                "lang=en; Max-Age=3600; domain=backend.internal; HttpOnly",
+            ]
+        )
+        .await,
+        [
+            "id=a3fWa; Domain=example.com",
+            "theme=dark; Domain=other.org",
+            "lang=en; Max-Age=3600; Domain=example.com; HttpOnly",
+        ]
+    );
+}
+
+async fn forwarded_cookies(ctx: &mut HttpTestContext, cookies: &[&str]) -> Vec<String> {
+    let captured = capture_request(ctx);
+    let mut request = Request::get("/");
+
+    for cookie in cookies {
+        request = request.header(COOKIE, *cookie);
+    }
+
+    proxy()
+        .strip_request_cookies(&["session", "csrf"])
+        .call(
+            client_ip(),
+            &forward_url(ctx),
+            request.body(Body::empty()).unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let captured = captured.lock().unwrap();
+    let cookies = captured[0]
+        .headers()
+        .get_all(COOKIE)
+        .iter()
+        .map(|value| value.to_str().unwrap().to_string())
+        .collect();
+    cookies
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_strip_some_request_cookies(ctx: &mut HttpTestContext) {
+    assert_eq!(
+        forwarded_cookies(ctx, &["theme=dark; session=abc", "csrf=123; lang=en"]).await,
+        ["theme=dark; lang=en"]
+    );
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_strip_all_request_cookies(ctx: &mut HttpTestContext) {
+    assert!(forwarded_cookies(ctx, &["session=abc; csrf=123"])
+        .await
+        .is_empty());
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_strip_request_cookies_without_cookie_header(ctx: &mut HttpTestContext) {
+    assert!(forwarded_cookies(ctx, &[]).await.is_empty());
+}
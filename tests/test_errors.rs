@@ -0,0 +1,104 @@
+use hyper::{Body, Client, Request, Response, StatusCode, Uri};
+use hyper_reverse_proxy::{ProxyError, ReverseProxy};
+use tokiotest_httpserver::take_port;
+
+fn build_response(status: u16) -> Result<Response<Body>, ProxyError> {
+    Ok(Response::builder().status(status).body(Body::empty())?)
+}
+
+#[test]
+fn test_http_error_converts_into_build_error() {
+    let result = build_response(1000);
+
+    assert!(
+        matches!(result, Err(ProxyError::BuildError(_))),
+        "expected BuildError, got {:?}",
+        result
+    );
+    assert_eq!(build_response(204).unwrap().status(), 204);
+}
+
+#[test]
+fn test_error_status() {
+    let cases = vec![
+        (
+            ProxyError::InvalidUri("\n".parse::<Uri>().unwrap_err()),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ),
+        (ProxyError::ForwardHeaderError, StatusCode::BAD_REQUEST),
+        (
+            ProxyError::UpgradeError("mismatch".to_string()),
+            StatusCode::BAD_GATEWAY,
+        ),
+        (
+            ProxyError::UpstreamUnavailable("refused".into()),
+            StatusCode::SERVICE_UNAVAILABLE,
+        ),
+        (
+            ProxyError::RequestBodyTooLarge,
+            StatusCode::PAYLOAD_TOO_LARGE,
+        ),
+        (ProxyError::InvalidPath, StatusCode::BAD_REQUEST),
+        (ProxyError::RequestBodyTimeout, StatusCode::REQUEST_TIMEOUT),
+        (ProxyError::DuplicateHost, StatusCode::BAD_REQUEST),
+        (ProxyError::NoUpstream, StatusCode::SERVICE_UNAVAILABLE),
+        (
+            ProxyError::CircuitOpen("http://backend".to_string()),
+            StatusCode::SERVICE_UNAVAILABLE,
+        ),
+        (
+            ProxyError::UpstreamBusy("http://backend".to_string()),
+            StatusCode::SERVICE_UNAVAILABLE,
+        ),
+        (ProxyError::ResponseHeadersTooLarge, StatusCode::BAD_GATEWAY),
+        (
+            ProxyError::AsteriskForm(hyper::Method::GET),
+            StatusCode::BAD_REQUEST,
+        ),
+        (ProxyError::Cancelled, StatusCode::SERVICE_UNAVAILABLE),
+        (ProxyError::ResponseBodyTooLarge, StatusCode::BAD_GATEWAY),
+        (
+            ProxyError::DisallowedScheme("ftp".to_string()),
+            StatusCode::FORBIDDEN,
+        ),
+        (
+            ProxyError::InvalidForwardUri {
+                uri: "/backend".to_string(),
+                reason: "missing scheme",
+            },
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ),
+        (
+            build_response(1000).unwrap_err(),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ),
+        (
+            ProxyError::ForbiddenAddress("10.0.0.5".parse().unwrap()),
+            StatusCode::FORBIDDEN,
+        ),
+    ];
+
+    for (err, status) in cases {
+        assert_eq!(err.status(), status, "unexpected status for {:?}", err);
+    }
+}
+
+#[tokio::test]
+async fn test_connect_error_into_response() {
+    let port = take_port();
+    let err = ReverseProxy::new(Client::new())
+        .call(
+            "127.0.0.1".parse().unwrap(),
+            &format!("http://127.0.0.1:{}", port),
+            Request::get("/").body(Body::empty()).unwrap(),
+        )
+        .await
+        .unwrap_err();
+
+    let resp: Response<Body> = err.into();
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(
+        hyper::body::to_bytes(resp.into_body()).await.unwrap(),
+        "Service Unavailable"
+    );
+}
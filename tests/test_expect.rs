@@ -0,0 +1,82 @@
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokiotest_httpserver::take_port;
+
+mod common;
+
+use common::{proxy, serve_proxy};
+
+async fn read_head(stream: &mut TcpStream) -> String {
+    let mut head = Vec::new();
+    let mut byte = [0u8; 1];
+
+    while !head.ends_with(b"\r\n\r\n") {
+        stream.read_exact(&mut byte).await.unwrap();
+        head.push(byte[0]);
+    }
+
+    String::from_utf8(head).unwrap()
+}
+
+/// Starts a backend which only answers `100 Continue` to a request expecting it, then echoes the
+/// five byte body.
+async fn continue_backend() -> u16 {
+    let port = take_port();
+    let listener = TcpListener::bind(("127.0.0.1", port)).await.unwrap();
+
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let head = read_head(&mut stream).await.to_ascii_lowercase();
+        assert!(
+            head.contains("\r\nexpect: 100-continue\r\n"),
+            "expect header was not forwarded: {:?}",
+            head
+        );
+
+        stream
+            .write_all(b"HTTP/1.1 100 Continue\r\n\r\n")
+            .await
+            .unwrap();
+
+        let mut body = [0u8; 5];
+        stream.read_exact(&mut body).await.unwrap();
+        stream
+            .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 5\r\n\r\n")
+            .await
+            .unwrap();
+        stream.write_all(&body).await.unwrap();
+    });
+
+    port
+}
+
+#[tokio::test]
+async fn test_expect_100_continue_end_to_end() {
+    let backend_port = continue_backend().await;
+    let port = serve_proxy(proxy(), format!("http://127.0.0.1:{}", backend_port));
+
+    let mut client = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    client
+        .write_all(b"POST /upload HTTP/1.1\r\nhost: localhost\r\nexpect: 100-continue\r\ncontent-length: 5\r\n\r\n")
+        .await
+        .unwrap();
+
+    // the body is only sent once the proxy asked for it
+    let interim = read_head(&mut client).await;
+    assert!(
+        interim.starts_with("HTTP/1.1 100 Continue\r\n"),
+        "unexpected interim response {:?}",
+        interim
+    );
+    client.write_all(b"hello").await.unwrap();
+
+    let head = read_head(&mut client).await;
+    assert!(
+        head.starts_with("HTTP/1.1 200 OK\r\n"),
+        "unexpected response {:?}",
+        head
+    );
+    let mut body = [0u8; 5];
+    client.read_exact(&mut body).await.unwrap();
+    assert_eq!(&body, b"hello");
+}
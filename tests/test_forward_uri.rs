@@ -0,0 +1,248 @@
+use futures::future::BoxFuture;
+use hyper::http::uri::Scheme;
+use hyper::service::Service;
+use hyper::{Body, Client, Method, Request, Response, StatusCode, Uri};
+use hyper_reverse_proxy::{build_forward_uri, ProxyError, ReverseProxy};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use test_context::test_context;
+use tokio::net::TcpStream;
+use tokiotest_httpserver::HttpTestContext;
+
+fn forward(forward_url: &str, request_uri: &str) -> String {
+    let request = Request::get(request_uri).body(Body::empty()).unwrap();
+
+    build_forward_uri(forward_url, &request)
+        .unwrap()
+        .to_string()
+}
+
+#[test]
+fn test_query_neither() {
+    assert_eq!(forward("http://backend/", "/path"), "http://backend/path");
+}
+
+#[test]
+fn test_query_both() {
+    assert_eq!(
+        forward("http://backend?a=1", "/path?b=2"),
+        "http://backend/path?a=1&b=2"
+    );
+}
+
+#[test]
+fn test_query_both_prefers_forward_url_value() {
+    assert_eq!(
+        forward("http://backend?a=1", "/path?a=2&b=2"),
+        "http://backend/path?a=1&b=2"
+    );
+}
+
+#[test]
+fn test_query_request_only() {
+    assert_eq!(
+        forward("http://backend", "/path?b=2"),
+        "http://backend/path?b=2"
+    );
+}
+
+#[test]
+fn test_query_forward_url_only() {
+    assert_eq!(
+        forward("http://backend?a=1", "/path"),
+        "http://backend/path?a=1"
+    );
+}
+
+#[test]
+fn test_empty_queries_emit_no_question_mark() {
+    assert_eq!(forward("http://backend?", "/path?"), "http://backend/path");
+}
+
+#[test]
+fn test_ipv6_literal_with_port_and_path() {
+    assert_eq!(
+        forward("http://[::1]:8080/base", "/x"),
+        "http://[::1]:8080/base/x"
+    );
+}
+
+#[test]
+fn test_ipv6_literal_with_port() {
+    assert_eq!(forward("http://[::1]:8080", "/x"), "http://[::1]:8080/x");
+    assert_eq!(forward("http://[::1]:8080/", "/x"), "http://[::1]:8080/x");
+}
+
+#[test]
+fn test_ipv6_literal_with_port_and_query() {
+    let uri = forward("http://[::1]:8080/base?a=1", "/x?b=2");
+
+    assert_eq!(uri, "http://[::1]:8080/base/x?a=1&b=2");
+    assert_eq!(uri.parse::<hyper::Uri>().unwrap().port_u16(), Some(8080));
+}
+
+#[test]
+fn test_effective_request_url_from_host_header() {
+    let request = Request::get("/users?page=2&sort=name")
+        .header("host", "example.com:8443")
+        .body(Body::empty())
+        .unwrap();
+
+    assert_eq!(
+        ReverseProxy::new(Client::new())
+            .effective_request_url("https", &request)
+            .as_deref(),
+        Some("https://example.com:8443/users?page=2&sort=name")
+    );
+}
+
+#[test]
+fn test_effective_request_url_without_host() {
+    let proxy = ReverseProxy::new(Client::new());
+
+    let request = Request::get("/users").body(Body::empty()).unwrap();
+    assert_eq!(proxy.effective_request_url("http", &request), None);
+
+    // the authority of the request URI does not stand in for the header
+    let request = Request::get("http://example.com/users")
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(proxy.effective_request_url("http", &request), None);
+}
+
+#[test]
+fn test_build_proxied_request_uses_inbound_scheme() {
+    let proxy = ReverseProxy::new(Client::new()).forward_inbound_scheme(true);
+    let request = Request::get("/path").body(Body::empty()).unwrap();
+
+    let proxied = proxy
+        .build_proxied_request(
+            "127.0.0.1".parse().unwrap(),
+            "http://backend",
+            request,
+            Some(Scheme::HTTPS),
+        )
+        .unwrap();
+
+    assert_eq!(proxied.uri(), "https://backend/path");
+}
+
+#[test]
+fn test_h3_scheme() {
+    assert_eq!(
+        forward("h3://backend:443/api", "/path?a=1"),
+        "h3://backend:443/api/path?a=1"
+    );
+}
+
+/// Stands in for a QUIC connector, records the uris it gets and connects over TCP.
+#[derive(Clone)]
+struct QuicStandIn {
+    port: u16,
+    seen: Arc<Mutex<Vec<String>>>,
+}
+
+impl Service<Uri> for QuicStandIn {
+    type Response = TcpStream;
+    type Error = std::io::Error;
+    type Future = BoxFuture<'static, std::io::Result<TcpStream>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        self.seen.lock().unwrap().push(uri.to_string());
+        Box::pin(TcpStream::connect(("127.0.0.1", self.port)))
+    }
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_h3_scheme_reaches_connector(ctx: &mut HttpTestContext) {
+    ctx.add(Arc::new(|req: Request<Body>| {
+        Box::pin(async move { Ok(Response::new(Body::from(req.uri().to_string()))) })
+    }));
+    let port = ctx.port;
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let seen_by_connector = seen.clone();
+    let connector = QuicStandIn {
+        port,
+        seen: seen_by_connector,
+    };
+
+    let resp = ReverseProxy::new(Client::builder().build::<_, Body>(connector))
+        .allowed_schemes(&["h3"])
+        .call(
+            "127.0.0.1".parse().unwrap(),
+            "h3://backend:443",
+            Request::get("/path").body(Body::empty()).unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(*seen.lock().unwrap(), ["h3://backend:443/"]);
+}
+
+#[test]
+fn test_asterisk_form_forwards_to_forward_url() {
+    assert_eq!(forward("http://backend/base", "*"), "http://backend/base");
+    assert_eq!(forward("http://backend/", "*"), "http://backend/");
+}
+
+#[test]
+fn test_empty_path_forwards_to_forward_url() {
+    // the authority-form has an empty path
+    assert_eq!(
+        forward("http://backend/base", "example.com:80"),
+        "http://backend/base"
+    );
+    assert_eq!(
+        forward("http://backend", "example.com:80"),
+        "http://backend/"
+    );
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_options_asterisk_reaches_backend(ctx: &mut HttpTestContext) {
+    ctx.add(Arc::new(|req: Request<Body>| {
+        let seen = format!("{} {}", req.method(), req.uri());
+        Box::pin(async move { Ok(Response::new(Body::from(seen))) })
+    }));
+
+    let resp = ReverseProxy::new(Client::new())
+        .call(
+            "127.0.0.1".parse().unwrap(),
+            &format!("http://127.0.0.1:{}/base", ctx.port),
+            Request::builder()
+                .method(Method::OPTIONS)
+                .uri("*")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+    assert_eq!(body, "OPTIONS /base");
+}
+
+#[tokio::test]
+async fn test_asterisk_form_rejected_for_other_methods() {
+    let result = ReverseProxy::new(Client::new())
+        .call(
+            "127.0.0.1".parse().unwrap(),
+            "http://127.0.0.1:1",
+            Request::get("*").body(Body::empty()).unwrap(),
+        )
+        .await;
+
+    assert!(
+        matches!(result, Err(ProxyError::AsteriskForm(Method::GET))),
+        "expected AsteriskForm, got {:?}",
+        result
+    );
+}
@@ -0,0 +1,768 @@
+use hyper::header::{HeaderName, HeaderValue, SERVER, VIA};
+use hyper::http::uri::Scheme;
+use hyper::{Body, Request, Response};
+use hyper_reverse_proxy::{
+    build_forwarded_for, ForwardedForLimit, ForwardedForSeparator, HeaderPolicy, InboundScheme,
+    ProxyError,
+};
+use std::sync::Arc;
+use test_context::test_context;
+use tokiotest_httpserver::HttpTestContext;
+
+mod common;
+
+use common::{capture_request, client_ip, forward_url, proxy, CapturedRequests};
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_forwarded_for_appends_client_ip(ctx: &mut HttpTestContext) {
+    let captured = capture_request(ctx);
+    let request = Request::get("/")
+        .header("x-forwarded-for", "10.0.0.1")
+        .body(Body::empty())
+        .unwrap();
+
+    proxy()
+        .call(client_ip(), &forward_url(ctx), request)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        captured.lock().unwrap()[0].headers()["x-forwarded-for"],
+        "10.0.0.1, 127.0.0.1"
+    );
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_custom_forwarded_for_header_vacant(ctx: &mut HttpTestContext) {
+    let captured = capture_request(ctx);
+
+    proxy()
+        .forwarded_for_header(HeaderName::from_static("cf-connecting-ip"))
+        .call(
+            client_ip(),
+            &forward_url(ctx),
+            Request::get("/").body(Body::empty()).unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let captured = captured.lock().unwrap();
+    assert_eq!(captured[0].headers()["cf-connecting-ip"], "127.0.0.1");
+    assert!(!captured[0].headers().contains_key("x-forwarded-for"));
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_custom_forwarded_for_header_occupied(ctx: &mut HttpTestContext) {
+    let captured = capture_request(ctx);
+    let request = Request::get("/")
+        .header("cf-connecting-ip", "10.0.0.1")
+        .body(Body::empty())
+        .unwrap();
+
+    proxy()
+        .forwarded_for_header(HeaderName::from_static("cf-connecting-ip"))
+        .call(client_ip(), &forward_url(ctx), request)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        captured.lock().unwrap()[0].headers()["cf-connecting-ip"],
+        "10.0.0.1, 127.0.0.1"
+    );
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_forwarded_for_kept_from_trusted_proxy(ctx: &mut HttpTestContext) {
+    let captured = capture_request(ctx);
+    let request = Request::get("/")
+        .header("x-forwarded-for", "10.0.0.1")
+        .body(Body::empty())
+        .unwrap();
+
+    proxy()
+        .trusted_proxies(vec!["127.0.0.0/8".parse().unwrap()])
+        .call(client_ip(), &forward_url(ctx), request)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        captured.lock().unwrap()[0].headers()["x-forwarded-for"],
+        "10.0.0.1, 127.0.0.1"
+    );
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_forwarded_for_dropped_from_untrusted_peer(ctx: &mut HttpTestContext) {
+    let captured = capture_request(ctx);
+    let request = Request::get("/")
+        .header("x-forwarded-for", "10.0.0.1")
+        .body(Body::empty())
+        .unwrap();
+
+    proxy()
+        .trusted_proxies(vec!["192.168.0.0/16".parse().unwrap()])
+        .call(client_ip(), &forward_url(ctx), request)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        captured.lock().unwrap()[0].headers()["x-forwarded-for"],
+        "127.0.0.1"
+    );
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_via_first_hop(ctx: &mut HttpTestContext) {
+    let captured = capture_request(ctx);
+
+    let resp = proxy()
+        .via_pseudonym("edge".to_string())
+        .call(
+            client_ip(),
+            &forward_url(ctx),
+            Request::get("/").body(Body::empty()).unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(captured.lock().unwrap()[0].headers()[VIA], "1.1 edge");
+    assert_eq!(resp.headers()[VIA], "1.1 edge");
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_via_appends_to_existing_chain(ctx: &mut HttpTestContext) {
+    let captured: CapturedRequests = Default::default();
+    let to_move = captured.clone();
+    ctx.add(Arc::new(move |req: Request<Body>| {
+        let (parts, _) = req.into_parts();
+        to_move.lock().unwrap().push(Request::from_parts(parts, ()));
+
+        Box::pin(async {
+            Ok(Response::builder()
+                .header(VIA, "1.0 backend")
+                .body(Body::empty())
+                .unwrap())
+        })
+    }));
+
+    let request = Request::get("/")
+        .header(VIA, "1.0 fred")
+        .header(VIA, "1.1 p.example.net")
+        .body(Body::empty())
+        .unwrap();
+
+    let resp = proxy()
+        .via_pseudonym("edge".to_string())
+        .call(client_ip(), &forward_url(ctx), request)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        captured.lock().unwrap()[0].headers()[VIA],
+        "1.0 fred, 1.1 p.example.net, 1.1 edge"
+    );
+    assert_eq!(resp.headers()[VIA], "1.0 backend, 1.1 edge");
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_x_real_ip_set_to_client_ip(ctx: &mut HttpTestContext) {
+    let captured = capture_request(ctx);
+
+    proxy()
+        .set_x_real_ip(true)
+        .call(
+            client_ip(),
+            &forward_url(ctx),
+            Request::get("/").body(Body::empty()).unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        captured.lock().unwrap()[0].headers()["x-real-ip"],
+        "127.0.0.1"
+    );
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_x_real_ip_replaces_spoofed_value(ctx: &mut HttpTestContext) {
+    let captured = capture_request(ctx);
+    let request = Request::get("/")
+        .header("x-real-ip", "10.0.0.1")
+        .header("x-real-ip", "10.0.0.2")
+        .body(Body::empty())
+        .unwrap();
+
+    proxy()
+        .set_x_real_ip(true)
+        .call(client_ip(), &forward_url(ctx), request)
+        .await
+        .unwrap();
+
+    let captured = captured.lock().unwrap();
+    let values = captured[0]
+        .headers()
+        .get_all("x-real-ip")
+        .iter()
+        .collect::<Vec<_>>();
+    assert_eq!(values, ["127.0.0.1"]);
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_forwarded_for_chain_keeps_order(ctx: &mut HttpTestContext) {
+    let captured = capture_request(ctx);
+    let request = Request::get("/")
+        .header("x-forwarded-for", "203.0.113.7, 10.0.0.1, 10.0.0.2")
+        .body(Body::empty())
+        .unwrap();
+
+    proxy()
+        .trusted_proxies(vec!["127.0.0.0/8".parse().unwrap()])
+        .call(client_ip(), &forward_url(ctx), request)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        captured.lock().unwrap()[0].headers()["x-forwarded-for"],
+        "203.0.113.7, 10.0.0.1, 10.0.0.2, 127.0.0.1"
+    );
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_forwarded_for_joins_multiple_header_lines(ctx: &mut HttpTestContext) {
+    let captured = capture_request(ctx);
+    let request = Request::get("/")
+        .header("x-forwarded-for", "203.0.113.7")
+        .header("x-forwarded-for", "10.0.0.1, 10.0.0.2")
+        .body(Body::empty())
+        .unwrap();
+
+    proxy()
+        .call(client_ip(), &forward_url(ctx), request)
+        .await
+        .unwrap();
+
+    let captured = captured.lock().unwrap();
+    let values = captured[0]
+        .headers()
+        .get_all("x-forwarded-for")
+        .iter()
+        .collect::<Vec<_>>();
+    assert_eq!(values, ["203.0.113.7, 10.0.0.1, 10.0.0.2, 127.0.0.1"]);
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_header_allowlist_drops_unlisted_headers(ctx: &mut HttpTestContext) {
+    let captured = capture_request(ctx);
+    let allowed = vec![HeaderName::from_static("accept")]
+        .into_iter()
+        .collect();
+    let request = Request::post("/")
+        .header("accept", "text/html")
+        .header("x-internal-token", "secret")
+        .header("content-length", "5")
+        .body(Body::from("hello"))
+        .unwrap();
+
+    proxy()
+        .header_policy(HeaderPolicy::Allowlist(allowed))
+        .call(client_ip(), &forward_url(ctx), request)
+        .await
+        .unwrap();
+
+    let captured = captured.lock().unwrap();
+    let headers = captured[0].headers();
+    assert_eq!(headers["accept"], "text/html");
+    assert_eq!(headers["content-length"], "5");
+    assert!(!headers.contains_key("x-internal-token"));
+    // added by the proxy after filtering
+    assert_eq!(headers["x-forwarded-for"], "127.0.0.1");
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_header_policy_defaults_to_forwarding_all(ctx: &mut HttpTestContext) {
+    let captured = capture_request(ctx);
+    let request = Request::get("/")
+        .header("x-internal-token", "secret")
+        .body(Body::empty())
+        .unwrap();
+
+    proxy()
+        .call(client_ip(), &forward_url(ctx), request)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        captured.lock().unwrap()[0].headers()["x-internal-token"],
+        "secret"
+    );
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_disable_forwarded_for_adds_no_header(ctx: &mut HttpTestContext) {
+    let captured = capture_request(ctx);
+
+    proxy()
+        .disable_forwarded_for(true)
+        .call(
+            client_ip(),
+            &forward_url(ctx),
+            Request::get("/").body(Body::empty()).unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert!(!captured.lock().unwrap()[0]
+        .headers()
+        .contains_key("x-forwarded-for"));
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_disable_forwarded_for_strips_client_header(ctx: &mut HttpTestContext) {
+    let captured = capture_request(ctx);
+    let request = Request::get("/")
+        .header("x-forwarded-for", "10.0.0.1")
+        .body(Body::empty())
+        .unwrap();
+
+    proxy()
+        .disable_forwarded_for(true)
+        .call(client_ip(), &forward_url(ctx), request)
+        .await
+        .unwrap();
+
+    assert!(!captured.lock().unwrap()[0]
+        .headers()
+        .contains_key("x-forwarded-for"));
+}
+
+async fn served_by(ctx: &mut HttpTestContext, overwrite: bool) -> Response<Body> {
+    ctx.add(Arc::new(|_req: Request<Body>| {
+        Box::pin(async {
+            Ok(Response::builder()
+                .header(SERVER, "backend/1.0")
+                .body(Body::empty())
+                .unwrap())
+        })
+    }));
+
+    proxy()
+        .response_server_header("proxy-blue/2".to_string(), overwrite)
+        .call(
+            client_ip(),
+            &forward_url(ctx),
+            Request::get("/").body(Body::empty()).unwrap(),
+        )
+        .await
+        .unwrap()
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_response_server_header_is_set(ctx: &mut HttpTestContext) {
+    let resp = proxy()
+        .response_server_header("proxy-blue/2".to_string(), false)
+        .call(
+            client_ip(),
+            &forward_url(ctx),
+            Request::get("/").body(Body::empty()).unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(resp.headers()[SERVER], "proxy-blue/2");
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_response_server_header_appends_to_backend(ctx: &mut HttpTestContext) {
+    let resp = served_by(ctx, false).await;
+
+    assert_eq!(resp.headers()[SERVER], "backend/1.0 proxy-blue/2");
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_response_server_header_overwrites_backend(ctx: &mut HttpTestContext) {
+    let resp = served_by(ctx, true).await;
+
+    assert_eq!(resp.headers()[SERVER], "proxy-blue/2");
+}
+
+fn forwarded_chain() -> Request<Body> {
+    Request::get("/")
+        .header("x-forwarded-for", "10.0.0.1, 10.0.0.2")
+        .header("x-forwarded-for", "10.0.0.3")
+        .body(Body::empty())
+        .unwrap()
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_max_forwarded_for_entries_truncates_oldest(ctx: &mut HttpTestContext) {
+    let captured = capture_request(ctx);
+
+    proxy()
+        .max_forwarded_for_entries(2, ForwardedForLimit::Truncate)
+        .call(client_ip(), &forward_url(ctx), forwarded_chain())
+        .await
+        .unwrap();
+
+    assert_eq!(
+        captured.lock().unwrap()[0].headers()["x-forwarded-for"],
+        "10.0.0.2, 10.0.0.3, 127.0.0.1"
+    );
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_max_forwarded_for_entries_rejects(ctx: &mut HttpTestContext) {
+    let result = proxy()
+        .max_forwarded_for_entries(2, ForwardedForLimit::Reject)
+        .call(client_ip(), &forward_url(ctx), forwarded_chain())
+        .await;
+
+    assert!(
+        matches!(result, Err(ProxyError::ForwardHeaderError)),
+        "expected ForwardHeaderError, got {:?}",
+        result
+    );
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_max_forwarded_for_entries_keeps_short_chain(ctx: &mut HttpTestContext) {
+    let captured = capture_request(ctx);
+
+    proxy()
+        .max_forwarded_for_entries(3, ForwardedForLimit::Reject)
+        .call(client_ip(), &forward_url(ctx), forwarded_chain())
+        .await
+        .unwrap();
+
+    assert_eq!(
+        captured.lock().unwrap()[0].headers()["x-forwarded-for"],
+        "10.0.0.1, 10.0.0.2, 10.0.0.3, 127.0.0.1"
+    );
+}
+
+async fn forwarded_with_separator(
+    ctx: &mut HttpTestContext,
+    separator: ForwardedForSeparator,
+    existing: &str,
+) -> String {
+    let captured = capture_request(ctx);
+    let request = Request::get("/")
+        .header("x-forwarded-for", existing)
+        .body(Body::empty())
+        .unwrap();
+
+    proxy()
+        .forwarded_for_separator(separator)
+        .call(client_ip(), &forward_url(ctx), request)
+        .await
+        .unwrap();
+
+    let value = captured.lock().unwrap()[0].headers()["x-forwarded-for"]
+        .to_str()
+        .unwrap()
+        .to_string();
+    value
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_forwarded_for_separator_comma_space(ctx: &mut HttpTestContext) {
+    assert_eq!(
+        forwarded_with_separator(ctx, ForwardedForSeparator::CommaSpace, "10.0.0.1").await,
+        "10.0.0.1, 127.0.0.1"
+    );
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_forwarded_for_separator_comma(ctx: &mut HttpTestContext) {
+    assert_eq!(
+        forwarded_with_separator(ctx, ForwardedForSeparator::Comma, "10.0.0.1").await,
+        "10.0.0.1,127.0.0.1"
+    );
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_forwarded_for_separator_normalizes_mixed_entries(ctx: &mut HttpTestContext) {
+    assert_eq!(
+        forwarded_with_separator(
+            ctx,
+            ForwardedForSeparator::Comma,
+            "10.0.0.1,10.0.0.2, 10.0.0.3"
+        )
+        .await,
+        "10.0.0.1,10.0.0.2,10.0.0.3,127.0.0.1"
+    );
+}
+
+async fn forwarded_user_agent(
+    ctx: &mut HttpTestContext,
+    user_agent: Option<Option<String>>,
+) -> Option<String> {
+    let captured = capture_request(ctx);
+    let request = Request::get("/")
+        .header("user-agent", "curl/8.0")
+        .body(Body::empty())
+        .unwrap();
+
+    let proxy = match user_agent {
+        Some(user_agent) => proxy().set_user_agent(user_agent),
+        None => proxy(),
+    };
+    proxy
+        .call(client_ip(), &forward_url(ctx), request)
+        .await
+        .unwrap();
+
+    let user_agent = captured.lock().unwrap()[0]
+        .headers()
+        .get("user-agent")
+        .map(|value| value.to_str().unwrap().to_string());
+    user_agent
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_set_user_agent_overwrites(ctx: &mut HttpTestContext) {
+    assert_eq!(
+        forwarded_user_agent(ctx, Some(Some("proxy/1.0".to_string()))).await,
+        Some("proxy/1.0".to_string())
+    );
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_set_user_agent_strips(ctx: &mut HttpTestContext) {
+    assert_eq!(forwarded_user_agent(ctx, Some(None)).await, None);
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_user_agent_passes_through_by_default(ctx: &mut HttpTestContext) {
+    assert_eq!(
+        forwarded_user_agent(ctx, None).await,
+        Some("curl/8.0".to_string())
+    );
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_forwarding_headers_have_fixed_order(ctx: &mut HttpTestContext) {
+    let captured = capture_request(ctx);
+    // sent in reverse, with a removed header in between to shuffle the map
+    let request = Request::get("/")
+        .header(VIA, "1.0 fred")
+        .header("user-agent", "curl/7.0")
+        .header("x-real-ip", "10.0.0.1")
+        .header("x-forwarded-host", "spoofed.example")
+        .header("x-forwarded-proto", "gopher")
+        .header("x-forwarded-for", "10.0.0.2")
+        .header("host", "www.example.com")
+        .header("accept", "*/*")
+        .body(Body::empty())
+        .unwrap();
+
+    proxy()
+        .via_pseudonym("edge".to_string())
+        .set_x_real_ip(true)
+        .set_x_forwarded_proto(true)
+        .set_x_forwarded_host(true)
+        .set_user_agent(None)
+        .call(client_ip(), &forward_url(ctx), request)
+        .await
+        .unwrap();
+
+    let captured = captured.lock().unwrap();
+    let names = captured[0]
+        .headers()
+        .keys()
+        .map(|name| name.as_str())
+        .filter(|name| name.starts_with("x-") || *name == "via")
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        [
+            "x-forwarded-for",
+            "x-forwarded-proto",
+            "x-forwarded-host",
+            "x-real-ip",
+            "via"
+        ]
+    );
+    assert_eq!(
+        captured[0].headers()["x-forwarded-for"],
+        "10.0.0.2, 127.0.0.1"
+    );
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_forwarded_proto_and_host(ctx: &mut HttpTestContext) {
+    let captured = capture_request(ctx);
+    let mut request = Request::get("/")
+        .header("host", "www.example.com")
+        .header("x-forwarded-proto", "gopher")
+        .body(Body::empty())
+        .unwrap();
+    request
+        .extensions_mut()
+        .insert(InboundScheme(Scheme::HTTPS));
+
+    proxy()
+        .set_x_forwarded_proto(true)
+        .set_x_forwarded_host(true)
+        .call(client_ip(), &forward_url(ctx), request)
+        .await
+        .unwrap();
+
+    let captured = captured.lock().unwrap();
+    assert_eq!(captured[0].headers()["x-forwarded-proto"], "https");
+    assert_eq!(captured[0].headers()["x-forwarded-host"], "www.example.com");
+}
+
+async fn collapsed_forwarded_for(ctx: &mut HttpTestContext, chain: &str, client: &str) -> String {
+    let captured = capture_request(ctx);
+    let request = Request::get("/")
+        .header("x-forwarded-for", chain)
+        .body(Body::empty())
+        .unwrap();
+
+    proxy()
+        .trusted_proxies(vec![
+            "127.0.0.0/8".parse().unwrap(),
+            "10.1.0.0/16".parse().unwrap(),
+        ])
+        .collapse_forwarded_for(true)
+        .call(client.parse().unwrap(), &forward_url(ctx), request)
+        .await
+        .unwrap();
+
+    let value = captured.lock().unwrap()[0].headers()["x-forwarded-for"]
+        .to_str()
+        .unwrap()
+        .to_string();
+    value
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_collapse_forwarded_for_to_client(ctx: &mut HttpTestContext) {
+    assert_eq!(
+        collapsed_forwarded_for(ctx, "203.0.113.7, 10.1.0.1, 10.1.0.2", "127.0.0.1").await,
+        "203.0.113.7"
+    );
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_collapse_forwarded_for_ignores_spoofed_entries(ctx: &mut HttpTestContext) {
+    // the client prepended 198.51.100.1 itself, only the trusted hops are believed
+    assert_eq!(
+        collapsed_forwarded_for(ctx, "198.51.100.1, 203.0.113.7, 10.1.0.1", "127.0.0.1").await,
+        "203.0.113.7"
+    );
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_collapse_forwarded_for_from_untrusted_peer(ctx: &mut HttpTestContext) {
+    assert_eq!(
+        collapsed_forwarded_for(ctx, "203.0.113.7, 10.1.0.1", "192.0.2.1").await,
+        "192.0.2.1"
+    );
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_collapse_forwarded_for_all_trusted(ctx: &mut HttpTestContext) {
+    assert_eq!(
+        collapsed_forwarded_for(ctx, "10.1.0.1, 10.1.0.2", "127.0.0.1").await,
+        "10.1.0.1"
+    );
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_collapse_forwarded_for_without_trusted_proxies(ctx: &mut HttpTestContext) {
+    let captured = capture_request(ctx);
+    let request = Request::get("/")
+        .header("x-forwarded-for", "6.6.6.6")
+        .body(Body::empty())
+        .unwrap();
+
+    proxy()
+        .collapse_forwarded_for(true)
+        .call("203.0.113.9".parse().unwrap(), &forward_url(ctx), request)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        captured.lock().unwrap()[0].headers()["x-forwarded-for"],
+        "203.0.113.9"
+    );
+}
+
+#[test]
+fn test_build_forwarded_for_vacant() {
+    assert_eq!(build_forwarded_for(None, client_ip(), ", "), "127.0.0.1");
+    assert_eq!(
+        build_forwarded_for(Some(&HeaderValue::from_static("")), client_ip(), ", "),
+        "127.0.0.1"
+    );
+}
+
+#[test]
+fn test_build_forwarded_for_single_entry() {
+    let existing = HeaderValue::from_static("10.0.0.1");
+
+    assert_eq!(
+        build_forwarded_for(Some(&existing), client_ip(), ", "),
+        "10.0.0.1, 127.0.0.1"
+    );
+    assert_eq!(
+        build_forwarded_for(Some(&existing), client_ip(), ","),
+        "10.0.0.1,127.0.0.1"
+    );
+}
+
+#[test]
+fn test_build_forwarded_for_multiple_entries() {
+    let existing = HeaderValue::from_static("10.0.0.1, 10.0.0.2,10.0.0.3");
+
+    assert_eq!(
+        build_forwarded_for(Some(&existing), client_ip(), ", "),
+        "10.0.0.1, 10.0.0.2,10.0.0.3, 127.0.0.1"
+    );
+}
+
+#[test]
+fn test_build_forwarded_for_ipv6() {
+    let existing = HeaderValue::from_static("2001:db8::1");
+
+    assert_eq!(
+        build_forwarded_for(Some(&existing), "::1".parse().unwrap(), ", "),
+        "2001:db8::1, ::1"
+    );
+}
@@ -0,0 +1,91 @@
+use hyper::{Body, Request, Response, StatusCode};
+use hyper_reverse_proxy::{Forwarder, ProxyError};
+use std::sync::Mutex;
+use test_context::test_context;
+use tokiotest_httpserver::HttpTestContext;
+
+mod common;
+
+use common::{capture_request, client_ip, forward_url, proxy};
+
+/// Application code under test, only depending on `Forwarder`.
+async fn handle<F: Forwarder>(forwarder: &F, request: Request<Body>) -> Response<Body> {
+    if request.uri().path().starts_with("/api") {
+        match forwarder
+            .forward(client_ip(), "http://backend", request)
+            .await
+        {
+            Ok(response) => response,
+            Err(_) => Response::builder()
+                .status(StatusCode::BAD_GATEWAY)
+                .body(Body::empty())
+                .unwrap(),
+        }
+    } else {
+        Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::empty())
+            .unwrap()
+    }
+}
+
+#[derive(Default)]
+struct MockForwarder {
+    forwarded: Mutex<Vec<(String, String)>>,
+}
+
+impl Forwarder for MockForwarder {
+    fn forward<'a>(
+        &'a self,
+        _client_ip: std::net::IpAddr,
+        forward_uri: &'a str,
+        request: Request<Body>,
+    ) -> futures::future::BoxFuture<'a, Result<Response<Body>, ProxyError>> {
+        self.forwarded
+            .lock()
+            .unwrap()
+            .push((forward_uri.to_string(), request.uri().to_string()));
+
+        Box::pin(async { Ok(Response::new(Body::from("mocked"))) })
+    }
+}
+
+#[tokio::test]
+async fn test_mock_forwarder_replaces_proxy() {
+    let mock = MockForwarder::default();
+
+    let resp = handle(
+        &mock,
+        Request::get("/api/users").body(Body::empty()).unwrap(),
+    )
+    .await;
+    let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+    assert_eq!(body, "mocked");
+
+    let resp = handle(&mock, Request::get("/other").body(Body::empty()).unwrap()).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    assert_eq!(
+        *mock.forwarded.lock().unwrap(),
+        [("http://backend".to_string(), "/api/users".to_string())]
+    );
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_reverse_proxy_forwards_as_call(ctx: &mut HttpTestContext) {
+    let captured = capture_request(ctx);
+    let forwarder: &dyn Forwarder = &proxy();
+
+    let resp = forwarder
+        .forward(
+            client_ip(),
+            &forward_url(ctx),
+            Request::get("/api").body(Body::empty()).unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(captured.lock().unwrap()[0].uri(), "/api");
+}
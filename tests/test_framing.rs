@@ -0,0 +1,58 @@
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+mod common;
+
+use common::{proxy, raw_backend, serve_proxy};
+
+const CLOSE_DELIMITED: &[u8] =
+    b"HTTP/1.0 200 OK\r\nconnection: close\r\ncontent-type: text/plain\r\n\r\nclose-delimited body";
+
+async fn raw_call(port: u16, request: &[u8]) -> String {
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    stream.write_all(request).await.unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.unwrap();
+
+    String::from_utf8(response).unwrap().to_lowercase()
+}
+
+#[tokio::test]
+async fn test_close_delimited_http10_response_is_chunked() {
+    let backend_port = raw_backend(CLOSE_DELIMITED).await;
+    let port = serve_proxy(proxy(), format!("http://127.0.0.1:{}", backend_port));
+
+    let response = raw_call(
+        port,
+        b"GET / HTTP/1.1\r\nhost: proxy\r\nconnection: close\r\n\r\n",
+    )
+    .await;
+
+    assert!(response.starts_with("http/1.1 200 ok\r\n"), "{}", response);
+    assert!(
+        response.contains("\r\ntransfer-encoding: chunked\r\n"),
+        "{}",
+        response
+    );
+    assert!(
+        response.ends_with("\r\n\r\n14\r\nclose-delimited body\r\n0\r\n\r\n"),
+        "{}",
+        response
+    );
+}
+
+#[tokio::test]
+async fn test_close_delimited_response_to_http10_client() {
+    let backend_port = raw_backend(CLOSE_DELIMITED).await;
+    let port = serve_proxy(proxy(), format!("http://127.0.0.1:{}", backend_port));
+
+    let response = raw_call(port, b"GET / HTTP/1.0\r\nhost: proxy\r\n\r\n").await;
+
+    assert!(response.starts_with("http/1.0 200 ok\r\n"), "{}", response);
+    assert!(!response.contains("transfer-encoding"), "{}", response);
+    assert!(
+        response.ends_with("\r\n\r\nclose-delimited body"),
+        "{}",
+        response
+    );
+}
@@ -1,12 +1,16 @@
 use hyper::client::connect::dns::GaiResolver;
 use hyper::client::HttpConnector;
-use hyper::header::{CONNECTION, UPGRADE};
+use hyper::header::{
+    ACCEPT_RANGES, CONNECTION, CONTENT_LENGTH, CONTENT_RANGE, HOST, IF_RANGE, RANGE,
+    SEC_WEBSOCKET_PROTOCOL, UPGRADE,
+};
 use hyper::server::conn::AddrStream;
 use hyper::service::{make_service_fn, service_fn};
-use hyper::{Body, Client, Request, Response, Server, StatusCode, Uri};
+use hyper::{Body, Client, HeaderMap, Request, Response, Server, StatusCode, Uri};
 use hyper_reverse_proxy::ReverseProxy;
 use std::convert::Infallible;
 use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
 use test_context::test_context;
 use test_context::AsyncTestContext;
 use tokio::sync::oneshot::Sender;
@@ -100,6 +104,120 @@ async fn test_get(ctx: &mut ProxyTestContext) {
     assert_eq!(200, resp.status());
 }
 
+#[test_context(ProxyTestContext)]
+#[tokio::test]
+async fn test_range_request_passes_through(ctx: &mut ProxyTestContext) {
+    ctx.http_back.add(Arc::new(|req: Request<Body>| {
+        Box::pin(async move {
+            // only answer partially when both range headers arrived
+            let status = match (req.headers().get(RANGE), req.headers().get(IF_RANGE)) {
+                (Some(range), Some(if_range)) if range == "bytes=0-99" && if_range == "\"v1\"" => {
+                    StatusCode::PARTIAL_CONTENT
+                }
+                _ => StatusCode::OK,
+            };
+
+            Ok(Response::builder()
+                .status(status)
+                .header(ACCEPT_RANGES, "bytes")
+                .header(CONTENT_RANGE, "bytes 0-99/10000")
+                .body(Body::from(vec![b'x'; 100]))
+                .unwrap())
+        })
+    }));
+
+    let resp = Client::new()
+        .request(
+            Request::get(ctx.uri("/video.mp4"))
+                .header(RANGE, "bytes=0-99")
+                .header(IF_RANGE, "\"v1\"")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(resp.headers()[ACCEPT_RANGES], "bytes");
+    assert_eq!(resp.headers()[CONTENT_RANGE], "bytes 0-99/10000");
+    assert_eq!(resp.headers()[CONTENT_LENGTH], "100");
+    assert_eq!(
+        hyper::body::to_bytes(resp.into_body()).await.unwrap().len(),
+        100
+    );
+}
+
+fn switching_protocols(ctx: &mut ProxyTestContext, protocol: Option<&'static str>) {
+    ctx.http_back.add(Arc::new(move |_req: Request<Body>| {
+        Box::pin(async move {
+            let mut response = Response::builder()
+                .status(StatusCode::SWITCHING_PROTOCOLS)
+                .header(CONNECTION, "Upgrade")
+                .header(UPGRADE, "websocket");
+
+            if let Some(protocol) = protocol {
+                response = response.header(SEC_WEBSOCKET_PROTOCOL, protocol);
+            }
+
+            Ok(response.body(Body::empty()).unwrap())
+        })
+    }));
+}
+
+fn websocket_request(ctx: &ProxyTestContext, protocols: Option<&str>) -> Request<Body> {
+    let mut request = Request::builder()
+        .header(CONNECTION, "Upgrade")
+        .header(UPGRADE, "websocket")
+        .method("GET")
+        .uri(ctx.uri("/ws"));
+
+    if let Some(protocols) = protocols {
+        request = request.header(SEC_WEBSOCKET_PROTOCOL, protocols);
+    }
+
+    request.body(Body::empty()).unwrap()
+}
+
+#[test_context(ProxyTestContext)]
+#[tokio::test]
+async fn test_websocket_protocol_offered(ctx: &mut ProxyTestContext) {
+    switching_protocols(ctx, Some("chat"));
+
+    let resp = Client::new()
+        .request(websocket_request(ctx, Some("chat, superchat")))
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), 101);
+    assert_eq!(resp.headers()[SEC_WEBSOCKET_PROTOCOL], "chat");
+}
+
+#[test_context(ProxyTestContext)]
+#[tokio::test]
+async fn test_websocket_protocol_not_offered(ctx: &mut ProxyTestContext) {
+    switching_protocols(ctx, Some("mqtt"));
+
+    let resp = Client::new()
+        .request(websocket_request(ctx, Some("chat, superchat")))
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), 502);
+}
+
+#[test_context(ProxyTestContext)]
+#[tokio::test]
+async fn test_websocket_without_protocol(ctx: &mut ProxyTestContext) {
+    switching_protocols(ctx, None);
+
+    let resp = Client::new()
+        .request(websocket_request(ctx, None))
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), 101);
+}
+
 async fn handle(
     client_ip: IpAddr,
     req: Request<Body>,
@@ -119,7 +237,7 @@ async fn handle(
 }
 
 #[async_trait::async_trait]
-impl<'a> AsyncTestContext for ProxyTestContext {
+impl AsyncTestContext for ProxyTestContext {
     async fn setup() -> ProxyTestContext {
         let http_back: HttpTestContext = AsyncTestContext::setup().await;
         let (sender, receiver) = tokio::sync::oneshot::channel::<()>();
@@ -148,7 +266,7 @@ impl<'a> AsyncTestContext for ProxyTestContext {
         }
     }
     async fn teardown(self) {
-        let _ = AsyncTestContext::teardown(self.http_back);
+        AsyncTestContext::teardown(self.http_back).await;
         let _ = self.sender.send(()).unwrap();
         let _ = tokio::join!(self.proxy_handler);
     }
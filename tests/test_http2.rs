@@ -0,0 +1,225 @@
+use hyper::body::Bytes;
+use hyper::server::conn::AddrStream;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Client, HeaderMap, Request, Response, Server};
+use hyper_reverse_proxy::ReverseProxy;
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use test_context::{test_context, AsyncTestContext};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+use tokiotest_httpserver::{release_port, take_port};
+
+/// A raw HTTP/2 (prior knowledge) backend answering every request with a body and trailers.
+///
+/// hyper neither receives HTTP/1 trailers nor lets its HTTP/2 server send a `Trailer` header,
+/// so the backend is built on `h2` directly.
+struct Http2TestContext {
+    server_handler: JoinHandle<()>,
+    port: u16,
+}
+
+fn trailers() -> HeaderMap {
+    let mut trailers = HeaderMap::new();
+    trailers.insert("x-checksum", "1234".parse().unwrap());
+    // transfer-encoding is rejected by h2 itself, so use a hop header h2 lets through
+    trailers.insert("proxy-authenticate", "Basic".parse().unwrap());
+    trailers
+}
+
+async fn serve(listener: TcpListener) {
+    while let Ok((stream, _)) = listener.accept().await {
+        tokio::spawn(async move {
+            let mut connection = h2::server::handshake(stream).await.unwrap();
+
+            while let Some(Ok((_request, mut respond))) = connection.accept().await {
+                let response = Response::builder()
+                    .header("trailer", "x-checksum, proxy-authenticate")
+                    .body(())
+                    .unwrap();
+                let mut stream = respond.send_response(response, false).unwrap();
+
+                stream.send_data("hello".into(), false).unwrap();
+                stream.send_trailers(trailers()).unwrap();
+            }
+        });
+    }
+}
+
+fn proxy() -> ReverseProxy<hyper::client::HttpConnector> {
+    ReverseProxy::new(Client::builder().http2_only(true).build_http())
+}
+
+#[test_context(Http2TestContext)]
+#[tokio::test]
+async fn test_hop_trailers_are_dropped(ctx: &mut Http2TestContext) {
+    let client_ip: IpAddr = "127.0.0.1".parse().unwrap();
+
+    let mut resp = proxy()
+        .call(
+            client_ip,
+            &format!("http://127.0.0.1:{}", ctx.port),
+            Request::get("/trailers").body(Body::empty()).unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(resp.headers()["trailer"], "x-checksum");
+
+    let body = hyper::body::to_bytes(resp.body_mut()).await.unwrap();
+    let trailers = hyper::body::HttpBody::trailers(resp.body_mut())
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(body, "hello");
+    assert_eq!(trailers["x-checksum"], "1234");
+    assert!(!trailers.contains_key("proxy-authenticate"));
+}
+
+#[test_context(Http2TestContext)]
+#[tokio::test]
+async fn test_body_maps_keep_trailers(ctx: &mut Http2TestContext) {
+    let client_ip: IpAddr = "127.0.0.1".parse().unwrap();
+    let uppercase = |chunk: Bytes| Bytes::from(chunk.to_ascii_uppercase());
+
+    for proxy in [
+        proxy().with_response_body_map(uppercase),
+        proxy().with_blocking_response_transform(uppercase),
+    ] {
+        let mut resp = proxy
+            .call(
+                client_ip,
+                &format!("http://127.0.0.1:{}", ctx.port),
+                Request::get("/trailers").body(Body::empty()).unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let body = hyper::body::to_bytes(resp.body_mut()).await.unwrap();
+        let trailers = hyper::body::HttpBody::trailers(resp.body_mut())
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(body, "HELLO");
+        assert_eq!(trailers["x-checksum"], "1234");
+        assert!(!trailers.contains_key("proxy-authenticate"));
+    }
+}
+
+#[test_context(Http2TestContext)]
+#[tokio::test]
+async fn test_trailers_reach_client(ctx: &mut Http2TestContext) {
+    let backend_port = ctx.port;
+    let make_svc = make_service_fn(move |conn: &AddrStream| {
+        let remote_addr = conn.remote_addr().ip();
+
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| async move {
+                match proxy()
+                    .call(
+                        remote_addr,
+                        &format!("http://127.0.0.1:{}", backend_port),
+                        req,
+                    )
+                    .await
+                {
+                    Ok(response) => Ok::<_, Infallible>(response),
+                    Err(_) => Ok(Response::builder().status(502).body(Body::empty()).unwrap()),
+                }
+            }))
+        }
+    });
+
+    let port = take_port();
+    let addr = SocketAddr::new("127.0.0.1".parse().unwrap(), port);
+    let proxy_handler = tokio::spawn(Server::bind(&addr).http2_only(true).serve(make_svc));
+
+    let mut resp = Client::builder()
+        .http2_only(true)
+        .build_http::<Body>()
+        .get(
+            format!("http://127.0.0.1:{}/trailers", port)
+                .parse()
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let body = hyper::body::to_bytes(resp.body_mut()).await.unwrap();
+    let trailers = hyper::body::HttpBody::trailers(resp.body_mut())
+        .await
+        .unwrap()
+        .unwrap();
+
+    proxy_handler.abort();
+    release_port(port);
+
+    assert_eq!(body, "hello");
+    assert_eq!(trailers["x-checksum"], "1234");
+    assert!(!trailers.contains_key("proxy-authenticate"));
+}
+
+#[tokio::test]
+async fn test_backend_server_push_is_refused() {
+    let port = take_port();
+    let listener = TcpListener::bind(("127.0.0.1", port)).await.unwrap();
+    let (pushed, push_result) = tokio::sync::oneshot::channel();
+
+    let backend = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut connection = h2::server::handshake(stream).await.unwrap();
+        let (_request, mut respond) = connection.accept().await.unwrap().unwrap();
+
+        let push = Request::get(format!("http://127.0.0.1:{}/style.css", port))
+            .body(())
+            .unwrap();
+        pushed.send(respond.push_request(push).is_ok()).unwrap();
+
+        let response = Response::builder().body(()).unwrap();
+        let mut stream = respond.send_response(response, false).unwrap();
+        stream.send_data("hello".into(), true).unwrap();
+
+        // drive the connection until the proxy is done with it
+        while connection.accept().await.is_some() {}
+    });
+
+    let resp = proxy()
+        .call(
+            "127.0.0.1".parse().unwrap(),
+            &format!("http://127.0.0.1:{}", port),
+            Request::get("/").body(Body::empty()).unwrap(),
+        )
+        .await
+        .unwrap();
+    let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+
+    backend.abort();
+    release_port(port);
+
+    assert!(
+        !push_result.await.unwrap(),
+        "expected the push to be refused"
+    );
+    assert_eq!(body, "hello");
+}
+
+#[async_trait::async_trait]
+impl AsyncTestContext for Http2TestContext {
+    async fn setup() -> Http2TestContext {
+        let port = take_port();
+        let listener = TcpListener::bind(("127.0.0.1", port)).await.unwrap();
+        let server_handler = tokio::spawn(serve(listener));
+
+        Http2TestContext {
+            server_handler,
+            port,
+        }
+    }
+
+    async fn teardown(self) {
+        self.server_handler.abort();
+        release_port(self.port);
+    }
+}
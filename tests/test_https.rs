@@ -0,0 +1,145 @@
+use hyper::client::HttpConnector;
+use hyper::header::HOST;
+use hyper::server::conn::Http;
+use hyper::service::service_fn;
+use hyper::{Body, Request, Response};
+use hyper_reverse_proxy::{PinnedResolver, ProxyError, ReverseProxy};
+use hyper_rustls::HttpsConnectorBuilder;
+use hyper_trust_dns::TrustDnsResolver;
+use std::convert::Infallible;
+use std::io::BufReader;
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::net::TcpListener;
+use tokio_rustls::rustls::client::{ServerCertVerified, ServerCertVerifier};
+use tokio_rustls::rustls::{
+    Certificate, ClientConfig, Error, PrivateKey, ServerConfig, ServerName,
+};
+use tokio_rustls::TlsAcceptor;
+use tokiotest_httpserver::take_port;
+
+fn tls_acceptor() -> TlsAcceptor {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(
+        &include_bytes!("certs/self_signed.crt")[..],
+    ))
+    .unwrap()
+    .into_iter()
+    .map(Certificate)
+    .collect();
+    let key = rustls_pemfile::pkcs8_private_keys(&mut BufReader::new(
+        &include_bytes!("certs/self_signed.key")[..],
+    ))
+    .unwrap()
+    .remove(0);
+
+    let config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certs, PrivateKey(key))
+        .unwrap();
+
+    TlsAcceptor::from(Arc::new(config))
+}
+
+#[tokio::test]
+async fn test_verify_upstream_bad_certificate() {
+    let port = take_port();
+    let listener = TcpListener::bind(("127.0.0.1", port)).await.unwrap();
+    let acceptor = tls_acceptor();
+
+    let tls_handler = tokio::spawn(async move {
+        if let Ok((stream, _)) = listener.accept().await {
+            // the client is expected to abort the handshake, as it does not trust the certificate
+            let _ = acceptor.accept(stream).await;
+        }
+    });
+
+    let proxy = ReverseProxy::with_connector(
+        TrustDnsResolver::default().into_rustls_webpki_https_connector(),
+    );
+
+    let result = proxy
+        .verify_upstream(&format!("https://localhost:{}", port))
+        .await;
+    let _ = tokio::join!(tls_handler);
+
+    match result {
+        Err(ProxyError::UpstreamUnavailable(err)) => {
+            assert!(
+                err.to_string().contains("invalid peer certificate"),
+                "expected a certificate error, got {}",
+                err
+            );
+        }
+        other => panic!("expected UpstreamUnavailable, got {:?}", other),
+    }
+}
+
+/// Accepts any certificate, the self-signed one is only valid for `localhost`.
+struct AcceptAnyCertificate;
+
+impl ServerCertVerifier for AcceptAnyCertificate {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+#[tokio::test]
+async fn test_pinned_resolver_keeps_sni_hostname() {
+    let port = take_port();
+    let listener = TcpListener::bind(("127.0.0.1", port)).await.unwrap();
+    let acceptor = tls_acceptor();
+
+    let tls_handler = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let stream = acceptor.accept(stream).await.unwrap();
+        let sni = stream.get_ref().1.sni_hostname().map(str::to_string);
+
+        let service = service_fn(|req: Request<Body>| async move {
+            let host = req.headers()[HOST].to_str().unwrap().to_string();
+            Ok::<_, Infallible>(Response::new(Body::from(host)))
+        });
+        Http::new().serve_connection(stream, service).await.unwrap();
+
+        sni
+    });
+
+    // `backend.invalid` never resolves, the connection only succeeds through the pin
+    let mut http = HttpConnector::new_with_resolver(
+        PinnedResolver::new().pin("backend.invalid", "127.0.0.1".parse().unwrap()),
+    );
+    http.enforce_http(false);
+    let tls_config = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(Arc::new(AcceptAnyCertificate))
+        .with_no_client_auth();
+    let https = HttpsConnectorBuilder::new()
+        .with_tls_config(tls_config)
+        .https_only()
+        .enable_http1()
+        .wrap_connector(http);
+
+    let response = ReverseProxy::with_connector(https)
+        .call(
+            "127.0.0.1".parse().unwrap(),
+            &format!("https://backend.invalid:{}", port),
+            Request::get("/").body(Body::empty()).unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    assert_eq!(body, format!("backend.invalid:{}", port));
+    assert_eq!(
+        tls_handler.await.unwrap().as_deref(),
+        Some("backend.invalid")
+    );
+}
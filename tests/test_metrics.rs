@@ -0,0 +1,82 @@
+#![cfg(feature = "metrics")]
+
+use hyper::{Body, Request, StatusCode};
+use hyper_reverse_proxy::{ProxyError, ProxyMetrics};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use test_context::test_context;
+use tokiotest_httpserver::{take_port, HttpTestContext};
+
+mod common;
+
+use common::{capture_request, client_ip, forward_url, proxy};
+
+#[derive(Default)]
+struct CountingMetrics {
+    requests: AtomicUsize,
+    server_errors: AtomicUsize,
+    errors: AtomicUsize,
+    upgrades: AtomicUsize,
+}
+
+impl ProxyMetrics for CountingMetrics {
+    fn record_request(&self, status: StatusCode) {
+        self.requests.fetch_add(1, Ordering::SeqCst);
+
+        if status.is_server_error() {
+            self.server_errors.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn record_error(&self, _error: &ProxyError) {
+        self.errors.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn record_upgrade(&self) {
+        self.upgrades.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_metrics_count_requests(ctx: &mut HttpTestContext) {
+    let metrics = Arc::new(CountingMetrics::default());
+    let proxy = proxy().with_metrics(metrics.clone());
+    capture_request(ctx);
+
+    // the first request is answered by the capturing handler, the second by the default 500
+    for _ in 0..2 {
+        proxy
+            .call(
+                client_ip(),
+                &forward_url(ctx),
+                Request::get("/").body(Body::empty()).unwrap(),
+            )
+            .await
+            .unwrap();
+    }
+
+    assert_eq!(metrics.requests.load(Ordering::SeqCst), 2);
+    assert_eq!(metrics.server_errors.load(Ordering::SeqCst), 1);
+    assert_eq!(metrics.errors.load(Ordering::SeqCst), 0);
+    assert_eq!(metrics.upgrades.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn test_metrics_count_errors() {
+    let metrics = Arc::new(CountingMetrics::default());
+    let port = take_port();
+
+    let result = proxy()
+        .with_metrics(metrics.clone())
+        .call(
+            client_ip(),
+            &format!("http://127.0.0.1:{}", port),
+            Request::get("/").body(Body::empty()).unwrap(),
+        )
+        .await;
+
+    assert!(result.is_err());
+    assert_eq!(metrics.requests.load(Ordering::SeqCst), 0);
+    assert_eq!(metrics.errors.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,42 @@
+#![cfg(feature = "regex")]
+
+use hyper::{Body, Request};
+use hyper_reverse_proxy::Regex;
+use test_context::test_context;
+use tokiotest_httpserver::HttpTestContext;
+
+mod common;
+
+use common::{capture_request, client_ip, forward_url, proxy};
+
+async fn rewritten_uri(ctx: &mut HttpTestContext, uri: &str) -> String {
+    let captured = capture_request(ctx);
+
+    proxy()
+        .path_rewrite(Regex::new("^/api/v1/(.*)").unwrap(), "/$1".to_string())
+        .call(
+            client_ip(),
+            &forward_url(ctx),
+            Request::get(uri).body(Body::empty()).unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let uri = captured.lock().unwrap()[0].uri().to_string();
+    uri
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_path_rewrite_substitutes_capture_group(ctx: &mut HttpTestContext) {
+    assert_eq!(
+        rewritten_uri(ctx, "/api/v1/users?page=2").await,
+        "/users?page=2"
+    );
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_path_rewrite_keeps_non_matching_path(ctx: &mut HttpTestContext) {
+    assert_eq!(rewritten_uri(ctx, "/static/app.js").await, "/static/app.js");
+}
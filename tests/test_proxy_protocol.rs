@@ -0,0 +1,101 @@
+use hyper_reverse_proxy::client_ip_from_proxy_protocol;
+use std::net::IpAddr;
+
+fn ip(value: &str) -> Option<IpAddr> {
+    Some(value.parse().unwrap())
+}
+
+fn v2(command: u8, family: u8, addresses: &[u8]) -> Vec<u8> {
+    let mut header = b"\r\n\r\n\0\r\nQUIT\n".to_vec();
+    header.push(command);
+    header.push(family);
+    header.extend_from_slice(&(addresses.len() as u16).to_be_bytes());
+    header.extend_from_slice(addresses);
+    header
+}
+
+#[test]
+fn test_v1_tcp4() {
+    assert_eq!(
+        client_ip_from_proxy_protocol(b"PROXY TCP4 192.0.2.10 198.51.100.1 56324 443\r\n"),
+        ip("192.0.2.10")
+    );
+}
+
+#[test]
+fn test_v1_tcp6_followed_by_request() {
+    assert_eq!(
+        client_ip_from_proxy_protocol(
+            b"PROXY TCP6 2001:db8::1 2001:db8::2 56324 443\r\nGET / HTTP/1.1\r\n\r\n"
+        ),
+        ip("2001:db8::1")
+    );
+}
+
+#[test]
+fn test_v1_unknown_and_malformed() {
+    for preamble in [
+        &b"PROXY UNKNOWN\r\n"[..],
+        b"PROXY TCP4 192.0.2.10 198.51.100.1 56324 443",
+        b"PROXY TCP4 2001:db8::1 198.51.100.1 56324 443\r\n",
+        b"PROXY TCP4 192.0.2.10 198.51.100.1 56324\r\n",
+        b"PROXY TCP4 192.0.2.10 198.51.100.1 56324 99999\r\n",
+        b"GET / HTTP/1.1\r\n\r\n",
+    ] {
+        assert_eq!(client_ip_from_proxy_protocol(preamble), None);
+    }
+}
+
+#[test]
+fn test_v2_ipv4() {
+    let mut addresses = vec![192, 0, 2, 10, 198, 51, 100, 1];
+    addresses.extend_from_slice(&56324u16.to_be_bytes());
+    addresses.extend_from_slice(&443u16.to_be_bytes());
+    let mut preamble = v2(0x21, 0x11, &addresses);
+    preamble.extend_from_slice(b"GET / HTTP/1.1\r\n\r\n");
+
+    assert_eq!(client_ip_from_proxy_protocol(&preamble), ip("192.0.2.10"));
+}
+
+#[test]
+fn test_v2_ipv6() {
+    let source: IpAddr = "2001:db8::1".parse().unwrap();
+    let destination: IpAddr = "2001:db8::2".parse().unwrap();
+    let mut addresses = Vec::new();
+    for address in [source, destination] {
+        if let IpAddr::V6(address) = address {
+            addresses.extend_from_slice(&address.octets());
+        }
+    }
+    addresses.extend_from_slice(&[0xdc, 0x04, 0x01, 0xbb]);
+
+    assert_eq!(
+        client_ip_from_proxy_protocol(&v2(0x21, 0x21, &addresses)),
+        Some(source)
+    );
+}
+
+#[test]
+fn test_v2_local_truncated_and_unix() {
+    let addresses = [192, 0, 2, 10, 198, 51, 100, 1, 0xdc, 0x04, 0x01, 0xbb];
+
+    // LOCAL command, e.g. a health check of the load balancer
+    assert_eq!(
+        client_ip_from_proxy_protocol(&v2(0x20, 0x11, &addresses)),
+        None
+    );
+    // announced length exceeds the available bytes
+    let mut truncated = v2(0x21, 0x11, &addresses);
+    truncated.truncate(truncated.len() - 1);
+    assert_eq!(client_ip_from_proxy_protocol(&truncated), None);
+    // address block too short for the family
+    assert_eq!(
+        client_ip_from_proxy_protocol(&v2(0x21, 0x11, &addresses[..8])),
+        None
+    );
+    // AF_UNIX carries no ip
+    assert_eq!(
+        client_ip_from_proxy_protocol(&v2(0x21, 0x31, &[0; 216])),
+        None
+    );
+}
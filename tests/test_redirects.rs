@@ -0,0 +1,235 @@
+use hyper::header::LOCATION;
+use hyper::{Body, Request, Response, StatusCode};
+use std::sync::{Arc, Mutex};
+use test_context::test_context;
+use tokiotest_httpserver::HttpTestContext;
+
+mod common;
+
+use common::{client_ip, forward_url, proxy};
+
+/// Registers `calls` backend handlers redirecting `/hop/<n>` to `/hop/<n + 1>` until `/hop/<last>`
+/// answers, `/loop` to itself and `/external` to another host. Returns the requested paths.
+fn redirecting_backend(
+    ctx: &mut HttpTestContext,
+    calls: usize,
+    last: u32,
+) -> Arc<Mutex<Vec<String>>> {
+    let paths = Arc::new(Mutex::new(Vec::new()));
+
+    for _ in 0..calls {
+        let paths = paths.clone();
+
+        ctx.add(Arc::new(move |req: Request<Body>| {
+            let path = req.uri().path().to_string();
+            paths.lock().unwrap().push(path.clone());
+
+            let location = match path.strip_prefix("/hop/") {
+                Some(hop) if hop.parse::<u32>().unwrap() < last => {
+                    // relative references resolve against the current path
+                    Some((hop.parse::<u32>().unwrap() + 1).to_string())
+                }
+                Some(_) => None,
+                None if path == "/loop" => Some("/loop".to_string()),
+                None => Some("http://other.example/".to_string()),
+            };
+
+            Box::pin(async move {
+                let response = match location {
+                    Some(location) => Response::builder()
+                        .status(StatusCode::FOUND)
+                        .header(LOCATION, location),
+                    None => Response::builder(),
+                };
+
+                Ok(response.body(Body::from("final")).unwrap())
+            })
+        }));
+    }
+
+    paths
+}
+
+async fn get(ctx: &HttpTestContext, path: &str, max_hops: usize) -> Response<Body> {
+    proxy()
+        .follow_internal_redirects(max_hops)
+        .call(
+            client_ip(),
+            &forward_url(ctx),
+            Request::get(path).body(Body::empty()).unwrap(),
+        )
+        .await
+        .unwrap()
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_follow_single_redirect(ctx: &mut HttpTestContext) {
+    let paths = redirecting_backend(ctx, 2, 2);
+
+    let resp = get(ctx, "/hop/1", 5).await;
+
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        hyper::body::to_bytes(resp.into_body()).await.unwrap(),
+        "final"
+    );
+    assert_eq!(*paths.lock().unwrap(), ["/hop/1", "/hop/2"]);
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_follow_redirect_chain(ctx: &mut HttpTestContext) {
+    let paths = redirecting_backend(ctx, 4, 4);
+
+    let resp = get(ctx, "/hop/1", 5).await;
+
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        *paths.lock().unwrap(),
+        ["/hop/1", "/hop/2", "/hop/3", "/hop/4"]
+    );
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_redirect_loop_stops_at_max_hops(ctx: &mut HttpTestContext) {
+    let paths = redirecting_backend(ctx, 4, 0);
+
+    let resp = get(ctx, "/loop", 3).await;
+
+    assert_eq!(resp.status(), StatusCode::FOUND);
+    assert_eq!(resp.headers()[LOCATION], "/loop");
+    assert_eq!(paths.lock().unwrap().len(), 4);
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_external_redirect_is_returned(ctx: &mut HttpTestContext) {
+    let paths = redirecting_backend(ctx, 1, 0);
+
+    let resp = get(ctx, "/external", 3).await;
+
+    assert_eq!(resp.status(), StatusCode::FOUND);
+    assert_eq!(resp.headers()[LOCATION], "http://other.example/");
+    assert_eq!(paths.lock().unwrap().len(), 1);
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_redirects_not_followed_by_default(ctx: &mut HttpTestContext) {
+    redirecting_backend(ctx, 1, 2);
+
+    let resp = proxy()
+        .call(
+            client_ip(),
+            &forward_url(ctx),
+            Request::get("/hop/1").body(Body::empty()).unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::FOUND);
+}
+
+/// The requested paths with query and their `x-proxy-signature`.
+type Requests = Arc<Mutex<Vec<(String, Option<String>)>>>;
+
+/// Registers a backend redirecting the first request to `location` and answering the second.
+fn redirect_once(ctx: &mut HttpTestContext, location: &'static str) -> Requests {
+    let requests = Arc::new(Mutex::new(Vec::new()));
+
+    for redirect in [Some(location), None] {
+        let requests = requests.clone();
+
+        ctx.add(Arc::new(move |req: Request<Body>| {
+            let signature = req
+                .headers()
+                .get("x-proxy-signature")
+                .map(|value| value.to_str().unwrap().to_string());
+            requests
+                .lock()
+                .unwrap()
+                .push((req.uri().to_string(), signature));
+
+            Box::pin(async move {
+                let response = match redirect {
+                    Some(location) => Response::builder()
+                        .status(StatusCode::FOUND)
+                        .header(LOCATION, location),
+                    None => Response::builder(),
+                };
+
+                Ok(response.body(Body::from("final")).unwrap())
+            })
+        }));
+    }
+
+    requests
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_redirect_to_query_keeps_the_path(ctx: &mut HttpTestContext) {
+    let requests = redirect_once(ctx, "?q=1");
+
+    let resp = get(ctx, "/search?q=0", 1).await;
+
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(requests.lock().unwrap()[1].0, "/search?q=1");
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_redirect_resolves_dot_segments(ctx: &mut HttpTestContext) {
+    let requests = redirect_once(ctx, "../x");
+
+    let resp = get(ctx, "/a/b/c", 1).await;
+
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(requests.lock().unwrap()[1].0, "/a/x");
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_redirect_dot_segments_stop_at_the_root(ctx: &mut HttpTestContext) {
+    let requests = redirect_once(ctx, "../../../x");
+
+    let resp = get(ctx, "/a/b", 1).await;
+
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(requests.lock().unwrap()[1].0, "/x");
+}
+
+#[cfg(feature = "signing")]
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_redirect_hops_are_signed_again(ctx: &mut HttpTestContext) {
+    let requests = redirect_once(ctx, "/next");
+    let signer = hyper_reverse_proxy::HmacSigner::new(b"secret").with_clock(|| 1_700_000_000);
+
+    let resp = proxy()
+        .follow_internal_redirects(1)
+        .with_request_signer(signer.clone())
+        .call(
+            client_ip(),
+            &forward_url(ctx),
+            Request::get("/first").body(Body::empty()).unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::OK);
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests[1].0, "/next");
+    assert_eq!(
+        requests[1].1.as_deref(),
+        Some(
+            format!(
+                "t=1700000000,sig={}",
+                signer.signature(&hyper::Method::GET, "/next", 1_700_000_000)
+            )
+            .as_str()
+        )
+    );
+}
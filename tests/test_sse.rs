@@ -0,0 +1,71 @@
+use hyper::body::HttpBody;
+use hyper::header::CONTENT_TYPE;
+use hyper::{Body, Request, Response};
+use std::sync::Arc;
+use std::time::Duration;
+use test_context::test_context;
+use tokio::sync::Semaphore;
+use tokio::time::timeout;
+use tokiotest_httpserver::HttpTestContext;
+
+mod common;
+
+use common::{client_ip, forward_url, proxy};
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_sse_events_are_forwarded_incrementally(ctx: &mut HttpTestContext) {
+    // the backend only emits the next event once the client received the previous one, so a
+    // buffering proxy never completes
+    let received = Arc::new(Semaphore::new(0));
+    let received_to_move = received.clone();
+    ctx.add(Arc::new(move |_req: Request<Body>| {
+        let received = received_to_move.clone();
+        let events = futures::stream::unfold(0, move |event| {
+            let received = received.clone();
+
+            async move {
+                if event == 3 {
+                    return None;
+                }
+                if event > 0 {
+                    received.acquire().await.unwrap().forget();
+                }
+
+                let data = format!("id: {}\ndata: tick\n\n", event);
+                Some((Ok::<_, std::io::Error>(data), event + 1))
+            }
+        });
+
+        Box::pin(async move {
+            Ok(Response::builder()
+                .header(CONTENT_TYPE, "text/event-stream")
+                .body(Body::wrap_stream(events))
+                .unwrap())
+        })
+    }));
+
+    let resp = proxy()
+        .call(
+            client_ip(),
+            &forward_url(ctx),
+            Request::get("/events").body(Body::empty()).unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.headers()[CONTENT_TYPE], "text/event-stream");
+
+    let mut body = resp.into_body();
+    for event in 0..3 {
+        let chunk = timeout(Duration::from_secs(1), body.data())
+            .await
+            .expect("event was not forwarded")
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(chunk, format!("id: {}\ndata: tick\n\n", event));
+        received.add_permits(1);
+    }
+
+    assert!(body.data().await.is_none());
+}
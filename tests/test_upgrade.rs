@@ -0,0 +1,427 @@
+use hyper::{Body, Request, Response, StatusCode};
+use hyper_reverse_proxy::{is_upgrade_response, ProxyError};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokiotest_httpserver::take_port;
+
+mod common;
+
+use common::{client_ip, proxy, raw_backend, serve_proxy};
+
+#[tokio::test]
+async fn test_backend_closing_upgraded_connection_first_is_clean() {
+    let backend_port = raw_backend(
+        b"HTTP/1.1 101 Switching Protocols\r\nconnection: upgrade\r\nupgrade: websocket\r\n\r\nbye",
+    )
+    .await;
+    let (results, mut completed) = mpsc::unbounded_channel();
+    let proxy = proxy().on_upgrade_complete(move |result| {
+        results.send(result).unwrap();
+    });
+    let port = serve_proxy(proxy, format!("http://127.0.0.1:{}", backend_port));
+
+    let mut client = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    client
+        .write_all(b"GET /ws HTTP/1.1\r\nhost: localhost\r\nconnection: upgrade\r\nupgrade: websocket\r\n\r\n")
+        .await
+        .unwrap();
+
+    let mut received = Vec::new();
+    client.read_to_end(&mut received).await.unwrap();
+    assert!(
+        received.ends_with(b"\r\n\r\nbye"),
+        "unexpected response {:?}",
+        String::from_utf8_lossy(&received)
+    );
+
+    // the backend is gone, writing to it must still end the session cleanly
+    let _ = client.write_all(b"late").await;
+    drop(client);
+
+    let (backend_to_client, _) = completed.recv().await.unwrap().unwrap();
+    assert_eq!(backend_to_client, 3);
+}
+
+#[test]
+fn test_is_upgrade_response() {
+    let switching = Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header("upgrade", "websocket")
+        .body(Body::empty())
+        .unwrap();
+    let ok = Response::builder()
+        .status(StatusCode::OK)
+        .header("upgrade", "websocket")
+        .body(Body::empty())
+        .unwrap();
+
+    assert!(is_upgrade_response(&switching));
+    assert!(!is_upgrade_response(&ok));
+}
+
+#[tokio::test]
+async fn test_connect_tunnels_to_authority() {
+    let echo_port = take_port();
+    let listener = TcpListener::bind(("127.0.0.1", echo_port)).await.unwrap();
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (mut read, mut write) = tokio::io::split(stream);
+        tokio::io::copy(&mut read, &mut write).await.unwrap();
+    });
+
+    // the forward url is ignored for CONNECT
+    let port = serve_proxy(
+        proxy().tunnel_connect(true),
+        "http://127.0.0.1:1".to_string(),
+    );
+
+    let mut client = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    let connect = format!(
+        "CONNECT 127.0.0.1:{0} HTTP/1.1\r\nhost: 127.0.0.1:{0}\r\n\r\n",
+        echo_port
+    );
+    client.write_all(connect.as_bytes()).await.unwrap();
+
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        head.push(client.read_u8().await.unwrap());
+    }
+    assert!(
+        head.starts_with(b"HTTP/1.1 200 OK\r\n"),
+        "unexpected response {:?}",
+        String::from_utf8_lossy(&head)
+    );
+
+    client.write_all(b"ping").await.unwrap();
+    let mut echoed = [0u8; 4];
+    client.read_exact(&mut echoed).await.unwrap();
+    assert_eq!(&echoed, b"ping");
+}
+
+fn connect_request(authority: &str) -> Request<Body> {
+    Request::connect(authority).body(Body::empty()).unwrap()
+}
+
+#[tokio::test]
+async fn test_connect_to_private_address_is_rejected() {
+    let result = proxy()
+        .tunnel_connect(true)
+        .block_private_addresses(true)
+        .call(
+            client_ip(),
+            "http://example.com",
+            connect_request("127.0.0.1:22"),
+        )
+        .await;
+
+    assert!(
+        matches!(&result, Err(ProxyError::ForbiddenAddress(ip)) if ip.to_string() == "127.0.0.1"),
+        "expected ForbiddenAddress, got {:?}",
+        result
+    );
+}
+
+#[tokio::test]
+async fn test_connect_runs_async_request_filter_first() {
+    let result = proxy()
+        .tunnel_connect(true)
+        .with_async_request_filter(|_req| Box::pin(async { Err(ProxyError::ForwardHeaderError) }))
+        .call(
+            client_ip(),
+            "http://example.com",
+            connect_request("127.0.0.1:1"),
+        )
+        .await;
+
+    assert!(
+        matches!(result, Err(ProxyError::ForwardHeaderError)),
+        "expected ForwardHeaderError, got {:?}",
+        result
+    );
+}
+
+#[tokio::test]
+async fn test_connect_to_unreachable_authority_opens_its_circuit() {
+    let proxy = proxy()
+        .tunnel_connect(true)
+        .circuit_breaker(1, Duration::from_secs(60));
+
+    let result = proxy
+        .call(
+            client_ip(),
+            "http://example.com",
+            connect_request("127.0.0.1:1"),
+        )
+        .await;
+    assert!(
+        matches!(&result, Err(ProxyError::ConnectError { uri, .. }) if uri == "127.0.0.1:1"),
+        "expected ConnectError, got {:?}",
+        result
+    );
+
+    let result = proxy
+        .call(
+            client_ip(),
+            "http://example.com",
+            connect_request("127.0.0.1:1"),
+        )
+        .await;
+    assert!(
+        matches!(&result, Err(ProxyError::CircuitOpen(uri)) if uri == "127.0.0.1:1"),
+        "expected CircuitOpen, got {:?}",
+        result
+    );
+}
+
+#[tokio::test]
+async fn test_upgrade_idle_timeout_closes_silent_connection() {
+    let backend_port = take_port();
+    let listener = TcpListener::bind(("127.0.0.1", backend_port))
+        .await
+        .unwrap();
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut request = Vec::new();
+        while !request.ends_with(b"\r\n\r\n") {
+            request.push(stream.read_u8().await.unwrap());
+        }
+        stream
+            .write_all(b"HTTP/1.1 101 Switching Protocols\r\nconnection: upgrade\r\nupgrade: websocket\r\n\r\n")
+            .await
+            .unwrap();
+
+        // stay silent, but keep the connection open
+        let mut rest = Vec::new();
+        let _ = stream.read_to_end(&mut rest).await;
+    });
+    let (results, mut completed) = mpsc::unbounded_channel();
+    let proxy = proxy()
+        .upgrade_idle_timeout(Duration::from_millis(100))
+        .on_upgrade_complete(move |result| {
+            results.send(result).unwrap();
+        });
+    let port = serve_proxy(proxy, format!("http://127.0.0.1:{}", backend_port));
+
+    let mut client = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    client
+        .write_all(b"GET /ws HTTP/1.1\r\nhost: localhost\r\nconnection: upgrade\r\nupgrade: websocket\r\n\r\n")
+        .await
+        .unwrap();
+
+    // the proxy closes the connection, ending the read
+    let mut received = Vec::new();
+    tokio::time::timeout(Duration::from_secs(5), client.read_to_end(&mut received))
+        .await
+        .expect("idle connection was not closed")
+        .unwrap();
+
+    let result = completed.recv().await.unwrap();
+    assert!(
+        matches!(&result, Err(err) if err.kind() == std::io::ErrorKind::TimedOut),
+        "expected TimedOut, got {:?}",
+        result
+    );
+}
+
+#[tokio::test]
+async fn test_switching_protocols_without_upgrade_request_is_an_error() {
+    for response in [
+        &b"HTTP/1.1 101 Switching Protocols\r\n\r\n"[..],
+        b"HTTP/1.1 101 Switching Protocols\r\nconnection: upgrade\r\nupgrade: websocket\r\n\r\n",
+    ] {
+        let backend_port = raw_backend(response).await;
+
+        let result = proxy()
+            .call(
+                "127.0.0.1".parse().unwrap(),
+                &format!("http://127.0.0.1:{}", backend_port),
+                Request::get("/").body(Body::empty()).unwrap(),
+            )
+            .await;
+
+        match result {
+            Err(ProxyError::UpgradeError(message)) => assert!(
+                message.contains("client did not request an upgrade"),
+                "unexpected message {:?}",
+                message
+            ),
+            result => panic!("expected UpgradeError, got {:?}", result),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_upgrade_mismatch_response() {
+    let backend_port = raw_backend(
+        b"HTTP/1.1 101 Switching Protocols\r\nconnection: upgrade\r\nupgrade: h2c\r\n\r\n",
+    )
+    .await;
+    // serve_proxy panics on errors, so this only passes if the mismatch is answered
+    let port = serve_proxy(
+        proxy().upgrade_mismatch_response(true),
+        format!("http://127.0.0.1:{}", backend_port),
+    );
+
+    let mut client = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    client
+        .write_all(b"GET /ws HTTP/1.1\r\nhost: localhost\r\nconnection: upgrade\r\nupgrade: websocket\r\n\r\n")
+        .await
+        .unwrap();
+
+    let mut received = vec![0; 1024];
+    let read = client.read(&mut received).await.unwrap();
+    let received = String::from_utf8_lossy(&received[..read]);
+    assert!(
+        received.starts_with("HTTP/1.1 502 Bad Gateway\r\n"),
+        "unexpected response {:?}",
+        received
+    );
+    assert!(
+        received.ends_with("backend tried to switch to protocol Some(\"h2c\") when Some(\"websocket\") was requested"),
+        "unexpected response {:?}",
+        received
+    );
+}
+
+#[tokio::test]
+async fn test_tunnel_with_large_upgrade_buffer_relays_both_directions() {
+    let echo_port = take_port();
+    let listener = TcpListener::bind(("127.0.0.1", echo_port)).await.unwrap();
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (mut read, mut write) = tokio::io::split(stream);
+        tokio::io::copy(&mut read, &mut write).await.unwrap();
+        write.shutdown().await.unwrap();
+    });
+    let port = serve_proxy(
+        proxy().tunnel_connect(true).upgrade_buffer_size(256 * 1024),
+        "http://127.0.0.1:1".to_string(),
+    );
+
+    let mut client = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    let connect = format!(
+        "CONNECT 127.0.0.1:{0} HTTP/1.1\r\nhost: 127.0.0.1:{0}\r\n\r\n",
+        echo_port
+    );
+    client.write_all(connect.as_bytes()).await.unwrap();
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        head.push(client.read_u8().await.unwrap());
+    }
+    assert!(head.starts_with(b"HTTP/1.1 200 OK\r\n"));
+
+    // more than fits into the buffers at once, so both directions have to keep flowing
+    let payload: Vec<u8> = (0..4 * 1024 * 1024)
+        .map(|index| (index % 251) as u8)
+        .collect();
+    let (mut read, mut write) = tokio::io::split(client);
+    let sent = payload.clone();
+    let writer = tokio::spawn(async move {
+        write.write_all(&sent).await.unwrap();
+        write.shutdown().await.unwrap();
+    });
+
+    let mut echoed = Vec::new();
+    read.read_to_end(&mut echoed).await.unwrap();
+    writer.await.unwrap();
+
+    assert_eq!(echoed.len(), payload.len());
+    assert!(echoed == payload);
+}
+
+#[tokio::test]
+async fn test_drain_waits_for_active_upgrades() {
+    use hyper::server::conn::AddrStream;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response};
+    use std::convert::Infallible;
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokiotest_httpserver::take_port;
+
+    // answers the upgrade request, then echoes until the client closes
+    let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0))
+        .await
+        .unwrap();
+    let backend_port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            head.push(stream.read_u8().await.unwrap());
+        }
+        stream
+            .write_all(b"HTTP/1.1 101 Switching Protocols\r\nconnection: upgrade\r\nupgrade: websocket\r\n\r\n")
+            .await
+            .unwrap();
+        let (mut read, mut write) = stream.split();
+        tokio::io::copy(&mut read, &mut write).await.unwrap();
+    });
+
+    let proxy = common::proxy();
+    let served = proxy.clone();
+    let make_svc = make_service_fn(move |conn: &AddrStream| {
+        let client_ip = conn.remote_addr().ip();
+        let proxy = served.clone();
+
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
+                let proxy = proxy.clone();
+
+                async move {
+                    let resp = proxy
+                        .call(
+                            client_ip,
+                            &format!("http://127.0.0.1:{}", backend_port),
+                            req,
+                        )
+                        .await
+                        .unwrap_or_else(|err| panic!("did not expect error: {:?}", err));
+
+                    Ok::<Response<Body>, Infallible>(resp)
+                }
+            }))
+        }
+    });
+    let port = take_port();
+    let server =
+        hyper::Server::bind(&std::net::SocketAddr::from(([127, 0, 0, 1], port))).serve(make_svc);
+    tokio::spawn(server);
+
+    // nothing to wait for yet
+    proxy.drain().await;
+    assert_eq!(proxy.active_upgrades(), 0);
+
+    let mut client = tokio::net::TcpStream::connect(("127.0.0.1", port))
+        .await
+        .unwrap();
+    client
+        .write_all(b"GET /ws HTTP/1.1\r\nhost: localhost\r\nconnection: upgrade\r\nupgrade: websocket\r\n\r\n")
+        .await
+        .unwrap();
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        head.push(client.read_u8().await.unwrap());
+    }
+    client.write_all(b"ping").await.unwrap();
+    let mut echoed = [0; 4];
+    client.read_exact(&mut echoed).await.unwrap();
+    assert_eq!(&echoed, b"ping");
+    assert_eq!(proxy.active_upgrades(), 1);
+
+    let drain = tokio::spawn({
+        let proxy = proxy.clone();
+        async move { proxy.drain().await }
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(!drain.is_finished());
+
+    drop(client);
+    tokio::time::timeout(Duration::from_secs(5), drain)
+        .await
+        .expect("drain resolves once the upgraded connection closed")
+        .unwrap();
+    assert_eq!(proxy.active_upgrades(), 0);
+}
@@ -0,0 +1,172 @@
+use hyper::{Body, Request, Response, StatusCode};
+use hyper_reverse_proxy::ProxyError;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use test_context::test_context;
+use tokiotest_httpserver::HttpTestContext;
+
+mod common;
+
+use common::{client_ip, forward_url, proxy};
+
+/// Registers `calls` backend handlers answering after `delay`, returning the highest number of
+/// requests in flight at once.
+fn slow_backend(ctx: &mut HttpTestContext, calls: usize, delay: Duration) -> Arc<AtomicUsize> {
+    let in_flight = Arc::new(AtomicUsize::new(0));
+    let max_in_flight = Arc::new(AtomicUsize::new(0));
+
+    for _ in 0..calls {
+        let in_flight = in_flight.clone();
+        let max_in_flight = max_in_flight.clone();
+
+        ctx.add(Arc::new(move |_req: Request<Body>| {
+            let in_flight = in_flight.clone();
+            let max_in_flight = max_in_flight.clone();
+
+            Box::pin(async move {
+                let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                max_in_flight.fetch_max(current, Ordering::SeqCst);
+                tokio::time::sleep(delay).await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+
+                Ok(Response::new(Body::empty()))
+            })
+        }));
+    }
+
+    max_in_flight
+}
+
+fn get() -> Request<Body> {
+    Request::get("/").body(Body::empty()).unwrap()
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_max_concurrent_per_upstream_bounds_in_flight_calls(ctx: &mut HttpTestContext) {
+    let max_in_flight = slow_backend(ctx, 6, Duration::from_millis(100));
+    let forward_url = forward_url(ctx);
+    let proxy = proxy().max_concurrent_per_upstream(2, Some(Duration::from_secs(5)));
+
+    let results =
+        futures::future::join_all((0..6).map(|_| proxy.call(client_ip(), &forward_url, get())))
+            .await;
+
+    for result in results {
+        assert_eq!(result.unwrap().status(), StatusCode::OK);
+    }
+    assert_eq!(max_in_flight.load(Ordering::SeqCst), 2);
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_max_concurrent_per_upstream_fails_fast_without_timeout(ctx: &mut HttpTestContext) {
+    slow_backend(ctx, 1, Duration::from_millis(200));
+    let forward_url = forward_url(ctx);
+    let proxy = proxy().max_concurrent_per_upstream(1, None);
+
+    let slow = proxy.call(client_ip(), &forward_url, get());
+    let rejected = async {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        proxy.call(client_ip(), &forward_url, get()).await
+    };
+    let (slow, rejected) = futures::future::join(slow, rejected).await;
+
+    assert_eq!(slow.unwrap().status(), StatusCode::OK);
+    assert!(
+        matches!(&rejected, Err(ProxyError::UpstreamBusy(uri)) if *uri == forward_url),
+        "expected UpstreamBusy, got {:?}",
+        rejected
+    );
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_max_concurrent_per_upstream_acquire_timeout(ctx: &mut HttpTestContext) {
+    slow_backend(ctx, 1, Duration::from_millis(300));
+    let forward_url = forward_url(ctx);
+    let proxy = proxy().max_concurrent_per_upstream(1, Some(Duration::from_millis(50)));
+
+    let (slow, rejected) = futures::future::join(
+        proxy.call(client_ip(), &forward_url, get()),
+        proxy.call(client_ip(), &forward_url, get()),
+    )
+    .await;
+
+    assert_eq!(slow.unwrap().status(), StatusCode::OK);
+    assert!(
+        matches!(rejected, Err(ProxyError::UpstreamBusy(_))),
+        "expected UpstreamBusy, got {:?}",
+        rejected
+    );
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_max_concurrent_per_upstream_ignores_the_path(ctx: &mut HttpTestContext) {
+    slow_backend(ctx, 1, Duration::from_millis(200));
+    let proxy = proxy().max_concurrent_per_upstream(1, None);
+
+    let (first, second) = (
+        format!("{}/a", forward_url(ctx)),
+        format!("{}/b", forward_url(ctx)),
+    );
+
+    let slow = proxy.call(client_ip(), &first, get());
+    let rejected = async {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        proxy.call(client_ip(), &second, get()).await
+    };
+    let (slow, rejected) = futures::future::join(slow, rejected).await;
+
+    assert_eq!(slow.unwrap().status(), StatusCode::OK);
+    assert!(
+        matches!(rejected, Err(ProxyError::UpstreamBusy(_))),
+        "expected UpstreamBusy, got {:?}",
+        rejected
+    );
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_max_concurrent_per_upstream_skips_filtered_calls(ctx: &mut HttpTestContext) {
+    slow_backend(ctx, 1, Duration::from_millis(10));
+    let forward_url = forward_url(ctx);
+    let proxy = proxy()
+        .max_concurrent_per_upstream(1, None)
+        .with_async_request_filter(|req| {
+            Box::pin(async move {
+                if req.uri().path() == "/rejected" {
+                    tokio::time::sleep(Duration::from_millis(200)).await;
+                    return Err(ProxyError::ForwardHeaderError);
+                }
+                Ok(())
+            })
+        });
+
+    // the slow rejection must not hold the only slot
+    let rejected = proxy.call(
+        client_ip(),
+        &forward_url,
+        Request::get("/rejected").body(Body::empty()).unwrap(),
+    );
+    let admitted = async {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        proxy.call(client_ip(), &forward_url, get()).await
+    };
+    let (rejected, admitted) = futures::future::join(rejected, admitted).await;
+
+    assert!(
+        matches!(rejected, Err(ProxyError::ForwardHeaderError)),
+        "expected ForwardHeaderError, got {:?}",
+        rejected
+    );
+    assert_eq!(admitted.unwrap().status(), StatusCode::OK);
+}
+
+#[test]
+#[should_panic(expected = "at least one slot")]
+fn test_max_concurrent_per_upstream_rejects_zero() {
+    let _ = proxy().max_concurrent_per_upstream(0, None);
+}
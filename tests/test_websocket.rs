@@ -2,6 +2,7 @@ use std::{
     convert::Infallible,
     net::{IpAddr, SocketAddr},
     process::exit,
+    sync::Mutex,
     time::Duration,
 };
 
@@ -21,10 +22,14 @@ use tungstenite::Message;
 use url::Url;
 
 lazy_static::lazy_static! {
+    static ref UPGRADE_BYTES: Mutex<Vec<(u64, u64)>> = Mutex::new(Vec::new());
     static ref  PROXY_CLIENT: ReverseProxy<HttpConnector<GaiResolver>> = {
         ReverseProxy::new(
             hyper::Client::new(),
         )
+        .on_upgrade_complete(|result| {
+            UPGRADE_BYTES.lock().unwrap().push(result.unwrap());
+        })
     };
 }
 
@@ -61,6 +66,37 @@ async fn test_websocket(ctx: &mut ProxyTestContext) {
     );
 }
 
+#[test_context(ProxyTestContext)]
+#[tokio::test]
+async fn test_websocket_upgrade_complete_callback(ctx: &mut ProxyTestContext) {
+    let (mut client, _) =
+        connect_async(Url::parse(&format!("ws://127.0.0.1:{}", ctx.port)).unwrap())
+            .await
+            .unwrap();
+
+    client.send(Message::Ping("hello".into())).await.unwrap();
+    client.next().await.unwrap().unwrap();
+    client.next().await.unwrap().unwrap();
+    drop(client);
+
+    loop {
+        let reported =
+            UPGRADE_BYTES
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|(backend_to_client, client_to_backend)| {
+                    *backend_to_client > 0 && *client_to_backend > 0
+                });
+
+        if reported {
+            break;
+        }
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
 async fn handle(
     client_ip: IpAddr,
     req: Request<Body>,
@@ -80,7 +116,7 @@ async fn handle(
 }
 
 #[async_trait::async_trait]
-impl<'a> AsyncTestContext for ProxyTestContext {
+impl AsyncTestContext for ProxyTestContext {
     async fn setup() -> ProxyTestContext {
         tokio::spawn(async {
             tokio::time::sleep(Duration::from_secs(5)).await;
@@ -0,0 +1,141 @@
+use hyper::{Body, Request, StatusCode};
+use hyper_reverse_proxy::ProxyError;
+use test_context::test_context;
+use tokiotest_httpserver::{take_port, HttpTestContext};
+
+mod common;
+
+use common::{capture_request, capture_request_into, client_ip, forward_url, proxy};
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_call_weighted_follows_weights(ctx: &mut HttpTestContext) {
+    let captured = capture_request(ctx);
+    for _ in 1..400 {
+        capture_request_into(ctx, &captured);
+    }
+    // one backend, the upstreams are told apart by their base path
+    let heavy = format!("{}/heavy", forward_url(ctx));
+    let light = format!("{}/light", forward_url(ctx));
+    let unused = format!("{}/unused", forward_url(ctx));
+    let upstreams = [
+        (heavy.as_str(), 3),
+        (light.as_str(), 1),
+        (unused.as_str(), 0),
+    ];
+    let proxy = proxy();
+
+    for _ in 0..400 {
+        let resp = proxy
+            .call_weighted(
+                client_ip(),
+                &upstreams,
+                Request::get("/").body(Body::empty()).unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    let captured = captured.lock().unwrap();
+    let count = |prefix: &str| {
+        captured
+            .iter()
+            .filter(|req| req.uri().path().starts_with(prefix))
+            .count()
+    };
+
+    // expected 300, far outside of chance when the weights were ignored
+    assert!(
+        (240..=360).contains(&count("/heavy")),
+        "heavy upstream got {} of 400 calls",
+        count("/heavy")
+    );
+    assert_eq!(count("/heavy") + count("/light"), 400);
+    assert_eq!(count("/unused"), 0);
+}
+
+#[test_context(HttpTestContext)]
+#[tokio::test]
+async fn test_call_weighted_fails_over_unreachable_upstream(ctx: &mut HttpTestContext) {
+    let captured = capture_request(ctx);
+    for _ in 1..5 {
+        capture_request_into(ctx, &captured);
+    }
+    let unreachable = format!("http://127.0.0.1:{}", take_port());
+    let reachable = forward_url(ctx);
+    let upstreams = [(unreachable.as_str(), 100), (reachable.as_str(), 1)];
+
+    for _ in 0..5 {
+        let resp = proxy()
+            .call_weighted(
+                client_ip(),
+                &upstreams,
+                Request::post("/upload")
+                    .body(Body::from("payload"))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    assert_eq!(captured.lock().unwrap().len(), 5);
+}
+
+#[tokio::test]
+async fn test_call_weighted_without_positive_weight() {
+    let result = proxy()
+        .call_weighted(
+            client_ip(),
+            &[("http://127.0.0.1:1", 0)],
+            Request::get("/").body(Body::empty()).unwrap(),
+        )
+        .await;
+
+    assert!(
+        matches!(result, Err(ProxyError::NoUpstream)),
+        "expected NoUpstream, got {:?}",
+        result
+    );
+}
+
+#[tokio::test]
+async fn test_call_weighted_fails_when_all_upstreams_are_unreachable() {
+    let first = format!("http://127.0.0.1:{}", take_port());
+    let second = format!("http://127.0.0.1:{}", take_port());
+
+    let result = proxy()
+        .call_weighted(
+            client_ip(),
+            &[(first.as_str(), 1), (second.as_str(), 1)],
+            Request::get("/").body(Body::empty()).unwrap(),
+        )
+        .await;
+
+    assert!(
+        matches!(result, Err(ProxyError::ConnectError { .. })),
+        "expected ConnectError, got {:?}",
+        result
+    );
+}
+
+#[tokio::test]
+async fn test_call_weighted_limits_buffered_body_by_default() {
+    // the body is rejected while buffering, before any upstream is contacted
+    let result = proxy()
+        .call_weighted(
+            client_ip(),
+            &[("http://127.0.0.1:1", 1)],
+            Request::post("/upload")
+                .body(Body::from(vec![0u8; 8 * 1024 * 1024 + 1]))
+                .unwrap(),
+        )
+        .await;
+
+    assert!(
+        matches!(result, Err(ProxyError::RequestBodyTooLarge)),
+        "expected RequestBodyTooLarge, got {:?}",
+        result
+    );
+}